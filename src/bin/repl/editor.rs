@@ -0,0 +1,721 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+const HISTORY_FILE: &str = ".rox_history";
+const HISTORY_SIZE: usize = 1000;
+
+/// What the user did at the prompt.
+#[derive(Debug, PartialEq)]
+pub enum Input {
+    Line(String),
+    /// Ctrl-C, the line being typed is abandoned.
+    Interrupted,
+    /// Ctrl-D on an empty line or the end of piped input.
+    Eof,
+}
+
+/// Reads lines from the terminal with cursor movement, history, reverse search (Ctrl-R) and tab
+/// completion. When stdin is not a terminal it reads plain lines, so that input can be piped.
+pub struct Editor {
+    history: History,
+    raw: bool,
+}
+
+impl Editor {
+    pub fn new() -> Editor {
+        let path = env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+        let raw = io::stdin().is_terminal() && io::stdout().is_terminal();
+        Editor {
+            history: if raw {
+                History::load(path)
+            } else {
+                History::default()
+            },
+            raw,
+        }
+    }
+
+    /// Shows `prompt` and reads a line, offering `completions` on Tab. The terminal leaves raw mode
+    /// before this returns, whether with a line or an error.
+    pub fn read_line(&mut self, prompt: &str, completions: &[String]) -> io::Result<Input> {
+        if !self.raw {
+            return self.read_plain(prompt);
+        }
+        let raw_mode = match RawMode::enable() {
+            Ok(raw_mode) => raw_mode,
+            Err(_) => {
+                self.raw = false;
+                return self.read_plain(prompt);
+            }
+        };
+
+        let stdin = io::stdin();
+        let mut keys = Keys::new(stdin.lock());
+        let mut stdout = io::stdout();
+        let mut line = Line::new(&self.history, completions);
+        write!(stdout, "{}", line.render(prompt))?;
+        stdout.flush()?;
+        let input = loop {
+            let key = match keys.next()? {
+                Some(key) => key,
+                None => break Input::Eof,
+            };
+            match line.handle(key) {
+                Action::Redraw => {}
+                Action::List(matches) => write!(stdout, "\r\n{}\r\n", matches.join("  "))?,
+                Action::Clear => write!(stdout, "\x1b[2J\x1b[H")?,
+                Action::Done(input) => break input,
+            }
+            write!(stdout, "{}", line.render(prompt))?;
+            stdout.flush()?;
+        };
+        if input == Input::Interrupted {
+            write!(stdout, "^C")?;
+        }
+        write!(stdout, "\r\n")?;
+        stdout.flush()?;
+        drop(raw_mode);
+
+        if let Input::Line(line) = &input {
+            self.history.add(line);
+        }
+        Ok(input)
+    }
+
+    fn read_plain(&mut self, prompt: &str) -> io::Result<Input> {
+        print!("{}", prompt);
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if io::stdin().lock().read_line(&mut line)? == 0 {
+            println!();
+            return Ok(Input::Eof);
+        }
+        let length = line.trim_end_matches(&['\n', '\r'][..]).len();
+        line.truncate(length);
+        Ok(Input::Line(line))
+    }
+}
+
+/// Switches the terminal to raw mode with `stty` and restores the previous settings when dropped.
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    fn enable() -> io::Result<RawMode> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        Ok(RawMode {
+            saved: saved.trim().to_string(),
+        })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("stty failed"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Previously entered lines, oldest first, appended to a file as they are entered.
+#[derive(Default)]
+struct History {
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    fn load(path: Option<PathBuf>) -> History {
+        let contents = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .unwrap_or_default();
+        let mut entries: Vec<String> = contents.lines().map(str::to_string).collect();
+        if entries.len() > HISTORY_SIZE {
+            entries.drain(..entries.len() - HISTORY_SIZE);
+            if let Some(path) = &path {
+                let _ = fs::write(path, entries.join("\n") + "\n");
+            }
+        }
+        History { entries, path }
+    }
+
+    fn add(&mut self, entry: &str) {
+        if entry.trim().is_empty() || self.entries.last().is_some_and(|last| last == entry) {
+            return;
+        }
+        self.entries.push(entry.to_string());
+        if let Some(path) = &self.path {
+            // Losing history is not worth interrupting the session for.
+            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+                let _ = writeln!(file, "{}", entry);
+            }
+        }
+    }
+
+    /// The newest entry before `before` that contains `query`.
+    fn search(&self, query: &str, before: usize) -> Option<usize> {
+        self.entries[..before]
+            .iter()
+            .rposition(|entry| entry.contains(query))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Key {
+    Char(char),
+    Ctrl(char),
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Escape,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    WordLeft,
+    WordRight,
+    Unknown,
+}
+
+/// Decodes key presses from the bytes a terminal sends in raw mode.
+struct Keys<R> {
+    input: R,
+    pending: Option<u8>,
+}
+
+impl<R: Read> Keys<R> {
+    fn new(input: R) -> Keys<R> {
+        Keys {
+            input,
+            pending: None,
+        }
+    }
+
+    fn next(&mut self) -> io::Result<Option<Key>> {
+        let byte = match self.byte()? {
+            Some(byte) => byte,
+            None => return Ok(None),
+        };
+        let key = match byte {
+            b'\r' | b'\n' => Key::Enter,
+            b'\t' => Key::Tab,
+            8 | 127 => Key::Backspace,
+            27 => self.escape()?,
+            1..=26 => Key::Ctrl((b'a' + byte - 1) as char),
+            0..=31 => Key::Unknown,
+            _ => self.utf8(byte)?,
+        };
+        Ok(Some(key))
+    }
+
+    fn byte(&mut self) -> io::Result<Option<u8>> {
+        if let Some(byte) = self.pending.take() {
+            return Ok(Some(byte));
+        }
+        let mut buffer = [0; 1];
+        loop {
+            match self.input.read(&mut buffer) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(buffer[0])),
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    // Arrows and the like arrive as `ESC [ parameters final` or `ESC O final`. A lone Escape is
+    // only known to be one when the next key arrives, which is then kept for the next call.
+    fn escape(&mut self) -> io::Result<Key> {
+        match self.byte()? {
+            Some(b'[') | Some(b'O') => {}
+            Some(other) => {
+                self.pending = Some(other);
+                return Ok(Key::Escape);
+            }
+            None => return Ok(Key::Escape),
+        }
+        let mut parameters = String::new();
+        let last = loop {
+            match self.byte()? {
+                Some(byte @ 0x40..=0x7e) => break byte,
+                Some(byte) => parameters.push(byte as char),
+                None => return Ok(Key::Unknown),
+            }
+        };
+        Ok(match (last, parameters.as_str()) {
+            (b'A', _) => Key::Up,
+            (b'B', _) => Key::Down,
+            (b'C', "1;5") => Key::WordRight,
+            (b'D', "1;5") => Key::WordLeft,
+            (b'C', _) => Key::Right,
+            (b'D', _) => Key::Left,
+            (b'H', _) | (b'~', "1") | (b'~', "7") => Key::Home,
+            (b'F', _) | (b'~', "4") | (b'~', "8") => Key::End,
+            (b'~', "3") => Key::Delete,
+            _ => Key::Unknown,
+        })
+    }
+
+    fn utf8(&mut self, first: u8) -> io::Result<Key> {
+        let length = match first {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 1,
+        };
+        let mut bytes = vec![first];
+        while bytes.len() < length {
+            match self.byte()? {
+                Some(byte) => bytes.push(byte),
+                None => break,
+            }
+        }
+        Ok(std::str::from_utf8(&bytes)
+            .ok()
+            .and_then(|text| text.chars().next())
+            .map_or(Key::Unknown, Key::Char))
+    }
+}
+
+/// What the terminal should do after a key press.
+#[derive(Debug, PartialEq)]
+enum Action {
+    Redraw,
+    /// Show the candidates of an ambiguous completion above the prompt.
+    List(Vec<String>),
+    Clear,
+    Done(Input),
+}
+
+struct Search {
+    query: String,
+    found: Option<usize>,
+}
+
+/// The line being edited, independent of the terminal so that it can be tested.
+struct Line<'a> {
+    history: &'a History,
+    completions: &'a [String],
+    buffer: Vec<char>,
+    cursor: usize,
+    // Which history entry is shown, and the line that was being typed before going back to it.
+    recalled: Option<usize>,
+    draft: Vec<char>,
+    search: Option<Search>,
+}
+
+impl<'a> Line<'a> {
+    fn new(history: &'a History, completions: &'a [String]) -> Line<'a> {
+        Line {
+            history,
+            completions,
+            buffer: vec![],
+            cursor: 0,
+            recalled: None,
+            draft: vec![],
+            search: None,
+        }
+    }
+
+    fn text(&self) -> String {
+        self.buffer.iter().collect()
+    }
+
+    fn set_text(&mut self, text: &str) {
+        self.buffer = text.chars().collect();
+        self.cursor = self.buffer.len();
+    }
+
+    /// The escape sequences that redraw the prompt and the line, with the cursor in place.
+    fn render(&self, prompt: &str) -> String {
+        if let Some(search) = &self.search {
+            let found = search
+                .found
+                .map_or("", |index| self.history.entries[index].as_str());
+            return format!("\r(reverse-i-search)'{}': {}\x1b[K", search.query, found);
+        }
+        let mut output = format!("\r{}{}\x1b[K\r", prompt, self.text());
+        let column = prompt.chars().count() + self.cursor;
+        if column > 0 {
+            output.push_str(&format!("\x1b[{}C", column));
+        }
+        output
+    }
+
+    fn handle(&mut self, key: Key) -> Action {
+        if self.search.is_some() {
+            if let Some(action) = self.handle_search(key) {
+                return action;
+            }
+        }
+        match key {
+            Key::Char(c) => {
+                self.buffer.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            Key::Enter => return Action::Done(Input::Line(self.text())),
+            Key::Tab => return self.complete(),
+            Key::Backspace | Key::Ctrl('h') if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.buffer.remove(self.cursor);
+            }
+            Key::Ctrl('d') if self.buffer.is_empty() => return Action::Done(Input::Eof),
+            Key::Delete | Key::Ctrl('d') if self.cursor < self.buffer.len() => {
+                self.buffer.remove(self.cursor);
+            }
+            Key::Left | Key::Ctrl('b') => self.cursor = self.cursor.saturating_sub(1),
+            Key::Right | Key::Ctrl('f') => self.cursor = (self.cursor + 1).min(self.buffer.len()),
+            Key::Home | Key::Ctrl('a') => self.cursor = 0,
+            Key::End | Key::Ctrl('e') => self.cursor = self.buffer.len(),
+            Key::WordLeft => self.cursor = self.word_start(),
+            Key::WordRight => self.cursor = self.word_end(),
+            Key::Up | Key::Ctrl('p') => self.previous(),
+            Key::Down | Key::Ctrl('n') => self.next(),
+            Key::Ctrl('k') => self.buffer.truncate(self.cursor),
+            Key::Ctrl('u') => {
+                self.buffer.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            Key::Ctrl('w') => {
+                let start = self.word_start();
+                self.buffer.drain(start..self.cursor);
+                self.cursor = start;
+            }
+            Key::Ctrl('c') => return Action::Done(Input::Interrupted),
+            Key::Ctrl('l') => return Action::Clear,
+            Key::Ctrl('r') => {
+                self.search = Some(Search {
+                    query: String::new(),
+                    found: None,
+                })
+            }
+            _ => {}
+        }
+        Action::Redraw
+    }
+
+    // Typing refines the search and Ctrl-R looks further back. Enter runs the match, Ctrl-G
+    // or Ctrl-C give up, and any other key keeps the match for editing before handling the key.
+    fn handle_search(&mut self, key: Key) -> Option<Action> {
+        let search = self.search.as_mut().expect("Not searching");
+        let entries = &self.history.entries;
+        match key {
+            Key::Char(c) => {
+                search.query.push(c);
+                let before = search.found.map_or(entries.len(), |index| index + 1);
+                search.found = self.history.search(&search.query, before);
+            }
+            Key::Backspace => {
+                search.query.pop();
+                search.found = self.history.search(&search.query, entries.len());
+            }
+            Key::Ctrl('r') => {
+                let before = search.found.unwrap_or(entries.len());
+                if let Some(index) = self.history.search(&search.query, before) {
+                    search.found = Some(index);
+                }
+            }
+            Key::Ctrl('g') | Key::Ctrl('c') => self.search = None,
+            _ => {
+                if let Some(index) = search.found {
+                    self.set_text(&entries[index]);
+                }
+                self.search = None;
+                return match key {
+                    Key::Enter => Some(Action::Done(Input::Line(self.text()))),
+                    Key::Escape => Some(Action::Redraw),
+                    _ => None,
+                };
+            }
+        }
+        Some(Action::Redraw)
+    }
+
+    fn previous(&mut self) {
+        let index = self.recalled.unwrap_or(self.history.entries.len());
+        if index == 0 {
+            return;
+        }
+        if self.recalled.is_none() {
+            self.draft = self.buffer.clone();
+        }
+        self.recalled = Some(index - 1);
+        self.set_text(&self.history.entries[index - 1]);
+    }
+
+    fn next(&mut self) {
+        match self.recalled {
+            Some(index) if index + 1 < self.history.entries.len() => {
+                self.recalled = Some(index + 1);
+                self.set_text(&self.history.entries[index + 1]);
+            }
+            Some(_) => {
+                self.recalled = None;
+                self.buffer = std::mem::take(&mut self.draft);
+                self.cursor = self.buffer.len();
+            }
+            None => {}
+        }
+    }
+
+    fn word_start(&self) -> usize {
+        let mut start = self.cursor;
+        while start > 0 && !is_word(self.buffer[start - 1]) {
+            start -= 1;
+        }
+        while start > 0 && is_word(self.buffer[start - 1]) {
+            start -= 1;
+        }
+        start
+    }
+
+    fn word_end(&self) -> usize {
+        let mut end = self.cursor;
+        while end < self.buffer.len() && !is_word(self.buffer[end]) {
+            end += 1;
+        }
+        while end < self.buffer.len() && is_word(self.buffer[end]) {
+            end += 1;
+        }
+        end
+    }
+
+    // Completes the word before the cursor as far as the candidates agree, and lists them when
+    // that does not get any further.
+    fn complete(&mut self) -> Action {
+        let mut start = self.cursor;
+        while start > 0 && is_word(self.buffer[start - 1]) {
+            start -= 1;
+        }
+        if start == self.cursor {
+            return Action::Redraw;
+        }
+        let prefix: String = self.buffer[start..self.cursor].iter().collect();
+        let mut matches: Vec<&String> = self
+            .completions
+            .iter()
+            .filter(|candidate| candidate.starts_with(&prefix))
+            .collect();
+        matches.sort();
+        matches.dedup();
+
+        let common = match matches.split_first() {
+            Some((first, rest)) => rest.iter().fold(first.as_str(), |common, candidate| {
+                let length = common
+                    .char_indices()
+                    .zip(candidate.chars())
+                    .take_while(|((_, a), b)| a == b)
+                    .last()
+                    .map_or(0, |((index, a), _)| index + a.len_utf8());
+                &common[..length]
+            }),
+            None => return Action::Redraw,
+        };
+        if common.len() > prefix.len() {
+            for c in common[prefix.len()..].chars() {
+                self.buffer.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            Action::Redraw
+        } else if matches.len() > 1 {
+            Action::List(matches.into_iter().cloned().collect())
+        } else {
+            Action::Redraw
+        }
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, History, Input, Key, Keys, Line};
+
+    fn history(entries: &[&str]) -> History {
+        History {
+            entries: entries.iter().map(|entry| entry.to_string()).collect(),
+            path: None,
+        }
+    }
+
+    fn type_keys(line: &mut Line, keys: &[Key]) -> Action {
+        let mut action = Action::Redraw;
+        for key in keys {
+            action = line.handle(*key);
+        }
+        action
+    }
+
+    fn chars(text: &str) -> Vec<Key> {
+        text.chars().map(Key::Char).collect()
+    }
+
+    #[test]
+    fn decode_keys() {
+        let bytes: &[u8] = b"a\x1b[A\x1b[1;5D\x1b[3~\x1bOH\x01\x7f\r\xc3\xa9\x1bx";
+        let mut keys = Keys::new(bytes);
+        let mut decoded = vec![];
+        while let Some(key) = keys.next().unwrap() {
+            decoded.push(key);
+        }
+        assert_eq!(
+            vec![
+                Key::Char('a'),
+                Key::Up,
+                Key::WordLeft,
+                Key::Delete,
+                Key::Home,
+                Key::Ctrl('a'),
+                Key::Backspace,
+                Key::Enter,
+                Key::Char('é'),
+                Key::Escape,
+                Key::Char('x'),
+            ],
+            decoded
+        );
+    }
+
+    #[test]
+    fn edit_line() {
+        let history = history(&[]);
+        let mut line = Line::new(&history, &[]);
+        type_keys(&mut line, &chars("print 1;"));
+        type_keys(&mut line, &[Key::Left, Key::Backspace]);
+        type_keys(&mut line, &chars("42"));
+        assert_eq!("print 42;", line.text());
+        type_keys(
+            &mut line,
+            &[Key::Home, Key::Delete, Key::Char('P'), Key::End],
+        );
+        assert_eq!("Print 42;", line.text());
+        type_keys(&mut line, &[Key::Ctrl('w')]);
+        assert_eq!("Print ", line.text());
+        type_keys(&mut line, &[Key::Ctrl('a'), Key::WordRight, Key::Ctrl('k')]);
+        assert_eq!("Print", line.text());
+        assert_eq!(
+            Action::Done(Input::Line("Print".to_string())),
+            line.handle(Key::Enter)
+        );
+        assert_eq!("\r> Print\x1b[K\r\x1b[7C", line.render("> "));
+
+        let mut line = Line::new(&history, &[]);
+        assert_eq!(Action::Done(Input::Eof), line.handle(Key::Ctrl('d')));
+        type_keys(&mut line, &chars("x"));
+        assert_eq!(
+            Action::Done(Input::Interrupted),
+            line.handle(Key::Ctrl('c'))
+        );
+    }
+
+    #[test]
+    fn recall_history() {
+        let history = history(&["var a = 1;", "print a;"]);
+        let mut line = Line::new(&history, &[]);
+        type_keys(&mut line, &chars("dra"));
+        type_keys(&mut line, &[Key::Up]);
+        assert_eq!("print a;", line.text());
+        type_keys(&mut line, &[Key::Up, Key::Up]);
+        assert_eq!("var a = 1;", line.text());
+        type_keys(&mut line, &[Key::Down]);
+        assert_eq!("print a;", line.text());
+        type_keys(&mut line, &[Key::Down]);
+        assert_eq!("dra", line.text());
+    }
+
+    #[test]
+    fn reverse_search() {
+        let history = history(&["var a = 1;", "print a;", "var b = 2;"]);
+        let mut line = Line::new(&history, &[]);
+        type_keys(&mut line, &[Key::Ctrl('r')]);
+        type_keys(&mut line, &chars("var"));
+        assert_eq!(
+            "\r(reverse-i-search)'var': var b = 2;\x1b[K",
+            line.render("> ")
+        );
+        type_keys(&mut line, &[Key::Ctrl('r')]);
+        assert_eq!(
+            "\r(reverse-i-search)'var': var a = 1;\x1b[K",
+            line.render("> ")
+        );
+        type_keys(&mut line, &[Key::Ctrl('r')]);
+        assert_eq!(
+            "\r(reverse-i-search)'var': var a = 1;\x1b[K",
+            line.render("> ")
+        );
+
+        // Other keys leave the search with the match to edit.
+        type_keys(&mut line, &[Key::End, Key::Backspace]);
+        assert_eq!("var a = 1", line.text());
+
+        let mut line = Line::new(&history, &[]);
+        type_keys(&mut line, &chars("typed"));
+        type_keys(&mut line, &[Key::Ctrl('r'), Key::Char('p'), Key::Ctrl('g')]);
+        assert_eq!("typed", line.text());
+
+        let mut line = Line::new(&history, &[]);
+        type_keys(&mut line, &[Key::Ctrl('r'), Key::Char('p')]);
+        assert_eq!(
+            Action::Done(Input::Line("print a;".to_string())),
+            line.handle(Key::Enter)
+        );
+    }
+
+    #[test]
+    fn complete_words() {
+        let history = history(&[]);
+        let completions: Vec<String> = ["print", "fun", "false", "fib", "fibonacci", "for"]
+            .iter()
+            .map(|word| word.to_string())
+            .collect();
+        let mut line = Line::new(&history, &completions);
+
+        type_keys(&mut line, &chars("pr"));
+        type_keys(&mut line, &[Key::Tab]);
+        assert_eq!("print", line.text());
+
+        type_keys(&mut line, &chars(" fi"));
+        assert_eq!(Action::Redraw, line.handle(Key::Tab));
+        assert_eq!("print fib", line.text());
+        assert_eq!(
+            Action::List(vec!["fib".to_string(), "fibonacci".to_string()]),
+            line.handle(Key::Tab)
+        );
+
+        type_keys(&mut line, &chars("(x"));
+        assert_eq!(Action::Redraw, line.handle(Key::Tab));
+        assert_eq!("print fib(x", line.text());
+    }
+
+    #[test]
+    fn history_skips_blank_and_repeated_lines() {
+        let mut history = history(&[]);
+        history.add("print 1;");
+        history.add("print 1;");
+        history.add("  ");
+        history.add("print 2;");
+        assert_eq!(vec!["print 1;", "print 2;"], history.entries);
+        assert_eq!(Some(0), history.search("1", 2));
+        assert_eq!(None, history.search("2", 1));
+    }
+}
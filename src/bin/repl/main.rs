@@ -0,0 +1,330 @@
+mod editor;
+
+use editor::{Editor, Input};
+use loxlib::{
+    check, disassemble, format_source, is_incomplete, print_ast, print_tokens, report_errors,
+    run_tests, Backend, ErrorFormat, Lox, LoxError, Phase, Token,
+};
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::process;
+use std::thread;
+use std::time::Instant;
+
+const HELP: &str = "\
+Usage: loxbin [options] [command] [file]
+
+Commands:
+  run <file>     Run a script, the default when only a file is given
+  repl           Start an interactive session, the default without arguments
+  tokens <file>  Print the tokens scanned from a script
+  ast <file>     Print the syntax tree parsed from a script
+  check <file>   Report compile errors without running the script
+  fmt <file>     Print a script laid out canonically
+  disasm <file>  Print the bytecode compiled from a script
+  test <path>    Check the scripts in <path> against their // expect: comments
+
+Options:
+  -e <code>                  Use <code> instead of a file
+  --error-format=human|json  How to report errors, human by default
+  --backend=tree|vm          What runs scripts, tests and the REPL: the tree-walking
+                             interpreter, the default, or the bytecode virtual machine
+  --trace                    Run on the virtual machine, printing its stack and each
+                             instruction to stderr before running it
+  --gc-stress                Run on the virtual machine, collecting garbage before every
+                             allocation
+  -h, --help                 Show this help
+
+A <file> of '-' reads the script from standard input.";
+
+// Exit codes from sysexits.h, as used by the reference implementation.
+const EX_USAGE: i32 = 64;
+const EX_DATAERR: i32 = 65;
+const EX_NOINPUT: i32 = 66;
+const EX_SOFTWARE: i32 = 70;
+
+// Enough for the tree-walking interpreter to reach its call depth limit, even in a debug build,
+// with half of it as its stack limit. Only the pages actually used are committed.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+const COMMANDS: &str = "\
+:tokens <code>  Show the tokens scanned from <code>
+:ast <code>     Show the syntax tree parsed from <code>
+:env            List the global variables and their values
+:load <file>    Run <file> in this session
+:reset          Forget everything declared so far
+:time <code>    Run <code> and show how long it took
+:help           Show this list
+Press Tab to complete a name, Ctrl-R to search the history and Ctrl-D to exit.";
+
+fn run_command(lox: &mut Lox, line: &str, session: &impl Fn() -> Lox, format: ErrorFormat) {
+    let (command, argument) = match line.find(char::is_whitespace) {
+        Some(index) => (&line[..index], line[index..].trim()),
+        None => (line, ""),
+    };
+    match command {
+        ":tokens" => report_brief(argument, print_tokens(argument)),
+        ":ast" => report_brief(argument, print_ast(argument, true)),
+        ":env" => {
+            for (name, value) in lox.globals() {
+                println!("{} = {}", name, value);
+            }
+        }
+        ":load" => {
+            if let Err(error) = lox.run_file(argument, format) {
+                eprintln!("Could not read '{}': {}", argument, error);
+            }
+        }
+        ":reset" => *lox = session(),
+        ":time" => {
+            let start = Instant::now();
+            lox.run_interactive(argument);
+            println!("Took {:?}.", start.elapsed());
+        }
+        ":help" => println!("{}", COMMANDS),
+        _ => eprintln!(
+            "Unknown command '{}', type :help for the list of commands.",
+            command
+        ),
+    }
+}
+
+fn report_brief(source: &str, result: Result<(), Vec<LoxError>>) {
+    for error in result.err().unwrap_or_default() {
+        eprintln!("{}", error.brief(source));
+    }
+}
+
+// Keywords and whatever the session has defined so far.
+fn completions(lox: &Lox) -> Vec<String> {
+    let keywords = Token::KEYWORDS.iter().map(|keyword| keyword.to_string());
+    keywords
+        .chain(lox.globals().into_iter().map(|(name, _)| name))
+        .collect()
+}
+
+// Lines are buffered until they form a complete input, so that a function or a block can be
+// typed over several lines. `session` starts the session, again on `:reset`; files loaded with
+// `:load` report their errors in `format`.
+fn replit(session: impl Fn() -> Lox, format: ErrorFormat) {
+    let mut lox = session();
+    let mut editor = Editor::new();
+    let mut input = String::new();
+    loop {
+        let prompt = if input.is_empty() { "> " } else { "... " };
+        let line = match editor.read_line(prompt, &completions(&lox)) {
+            Ok(Input::Line(line)) => line,
+            Ok(Input::Interrupted) => {
+                input.clear();
+                continue;
+            }
+            Ok(Input::Eof) => break,
+            // The terminal is already back to how it was, e.g. after a hangup.
+            Err(error) => {
+                eprintln!("Could not read input: {}.", error);
+                process::exit(EX_SOFTWARE);
+            }
+        };
+
+        // Commands only make sense on a line of their own.
+        if input.is_empty() && line.trim_start().starts_with(':') {
+            run_command(&mut lox, line.trim(), &session, format);
+            continue;
+        }
+
+        input.push_str(&line);
+        input.push('\n');
+        if is_incomplete(&input) {
+            continue;
+        }
+        if !input.trim().is_empty() {
+            lox.run_interactive(&input);
+        }
+        input.clear();
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Command {
+    Run,
+    Repl,
+    Tokens,
+    Ast,
+    Check,
+    Fmt,
+    Disasm,
+    Test,
+}
+
+impl Command {
+    fn from_name(name: &str) -> Option<Command> {
+        Some(match name {
+            "run" => Command::Run,
+            "repl" => Command::Repl,
+            "tokens" => Command::Tokens,
+            "ast" => Command::Ast,
+            "check" => Command::Check,
+            "fmt" => Command::Fmt,
+            "disasm" => Command::Disasm,
+            "test" => Command::Test,
+            _ => return None,
+        })
+    }
+}
+
+fn usage(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, HELP);
+    process::exit(EX_USAGE);
+}
+
+// The name errors are reported under, and the script itself.
+fn read_source(path: &str) -> (String, String) {
+    let result = if path == "-" {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source).map(|_| source)
+    } else {
+        fs::read_to_string(path)
+    };
+    match result {
+        Ok(source) if path == "-" => ("<stdin>".to_string(), source),
+        Ok(source) => (path.to_string(), source),
+        Err(error) => {
+            eprintln!("Could not read '{}': {}", path, error);
+            process::exit(EX_NOINPUT);
+        }
+    }
+}
+
+// Prints a line per test file, with a diff of the transcripts for the ones that failed.
+fn test(path: &str, session: impl Fn() -> Lox) {
+    let reports = match run_tests(Path::new(path), session) {
+        Ok(reports) => reports,
+        Err(error) => {
+            eprintln!("Could not read '{}': {}", path, error);
+            process::exit(EX_NOINPUT);
+        }
+    };
+    let mut failed = 0;
+    for report in reports.iter() {
+        match &report.diff {
+            None => println!("PASS {}", report.path.display()),
+            Some(diff) => {
+                failed += 1;
+                println!("FAIL {}", report.path.display());
+                for line in diff.lines() {
+                    println!("    {}", line);
+                }
+            }
+        }
+    }
+    println!("\n{} passed, {} failed.", reports.len() - failed, failed);
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+fn main() {
+    let cli = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(cli)
+        .expect("Failed to start the main thread");
+    // A panic was already reported by the thread itself.
+    if cli.join().is_err() {
+        process::exit(101);
+    }
+}
+
+fn cli() {
+    let mut format = ErrorFormat::Human;
+    let mut backend = Backend::Tree;
+    let mut trace = false;
+    let mut gc_stress = false;
+    let mut inline = None;
+    let mut positional = vec![];
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            println!("{}", HELP);
+            return;
+        } else if arg == "-e" {
+            match args.next() {
+                Some(code) if inline.is_none() => inline = Some(code),
+                Some(_) => usage("Only one -e is allowed."),
+                None => usage("Missing the code after -e."),
+            }
+        } else if let Some(value) = arg.strip_prefix("--error-format=") {
+            match value.parse() {
+                Ok(value) => format = value,
+                Err(message) => usage(&message),
+            }
+        } else if let Some(value) = arg.strip_prefix("--backend=") {
+            match value.parse() {
+                Ok(value) => backend = value,
+                Err(message) => usage(&message),
+            }
+        } else if arg == "--trace" {
+            trace = true;
+            backend = Backend::Vm;
+        } else if arg == "--gc-stress" {
+            gc_stress = true;
+            backend = Backend::Vm;
+        } else if arg.starts_with('-') && arg != "-" {
+            usage(&format!("Unknown option '{}'.", arg));
+        } else {
+            positional.push(arg);
+        }
+    }
+
+    let command = match positional.first().and_then(|name| Command::from_name(name)) {
+        Some(command) => {
+            positional.remove(0);
+            command
+        }
+        None if positional.is_empty() && inline.is_none() => Command::Repl,
+        None => Command::Run,
+    };
+    let session = || {
+        Lox::new()
+            .with_backend(backend)
+            .with_trace(trace)
+            .with_gc_stress(gc_stress)
+            .with_stack_limit(STACK_SIZE / 2)
+    };
+    if command == Command::Repl {
+        if !positional.is_empty() || inline.is_some() {
+            usage("The REPL does not take a file.");
+        }
+        replit(session, format);
+        return;
+    }
+    if command == Command::Test {
+        match (inline, positional.as_slice()) {
+            (None, [path]) => test(path, || session().with_trace(false)),
+            _ => usage("Expected the file or directory to test."),
+        }
+        return;
+    }
+
+    let (name, source) = match (inline, positional.len()) {
+        (Some(code), 0) => ("<inline>".to_string(), code),
+        (None, 1) => read_source(&positional[0]),
+        (None, 0) => usage("Missing the file to read."),
+        _ => usage("Expected a single file or -e <code>."),
+    };
+    let result = match command {
+        Command::Run => session().run(&source),
+        Command::Tokens => print_tokens(&source),
+        Command::Ast => print_ast(&source, false),
+        Command::Check => check(&source),
+        Command::Fmt => format_source(&source).map(|formatted| print!("{}", formatted)),
+        Command::Disasm => disassemble(&source).map(|listing| print!("{}", listing)),
+        Command::Repl | Command::Test => unreachable!(),
+    };
+    if let Err(errors) = result {
+        report_errors(&name, &source, &errors, format);
+        let runtime = errors.iter().any(|error| error.phase() == Phase::Runtime);
+        process::exit(if runtime { EX_SOFTWARE } else { EX_DATAERR });
+    }
+}
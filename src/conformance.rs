@@ -0,0 +1,189 @@
+use crate::Lox;
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// The outcome of running one test file.
+pub struct TestReport {
+    pub path: PathBuf,
+    /// The expected transcript against the actual one, `None` when they match.
+    pub diff: Option<String>,
+}
+
+impl TestReport {
+    pub fn passed(&self) -> bool {
+        self.diff.is_none()
+    }
+}
+
+/// Runs `path`, or every `.lox` file below it when it is a directory, and checks each against
+/// the annotations in its comments:
+///
+/// - `// expect: text` for a line the program prints,
+/// - `// expect runtime error: message` for the error that stops it on that line,
+/// - `// Error at 'x': message` or `// [line N] Error at 'x': message` for a compile error,
+///   reported on the same line or line N.
+///
+/// Each file runs in a fresh session made by `session`, e.g. to pick a backend, and reports come
+/// back sorted by path.
+pub fn run_tests(path: &Path, session: impl Fn() -> Lox) -> io::Result<Vec<TestReport>> {
+    let mut files = vec![];
+    collect(path, &mut files)?;
+    files.sort();
+    files
+        .into_iter()
+        .map(|path| run_test(&path, &session))
+        .collect()
+}
+
+fn collect(path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_dir() || path.extension().is_some_and(|extension| extension == "lox") {
+            collect(&path, files)?;
+        }
+    }
+    Ok(())
+}
+
+fn run_test(path: &Path, session: &impl Fn() -> Lox) -> io::Result<TestReport> {
+    let source = fs::read_to_string(path)?;
+    let expected = expectations(&source);
+
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let result = session().with_output(output.clone()).run(&source);
+    let output = String::from_utf8_lossy(&output.borrow()).into_owned();
+    let mut actual: Vec<String> = output.lines().map(str::to_string).collect();
+    for error in result.err().unwrap_or_default() {
+        actual.extend(error.brief(&source).lines().map(str::to_string));
+    }
+
+    Ok(TestReport {
+        path: path.to_path_buf(),
+        diff: if expected == actual {
+            None
+        } else {
+            Some(diff(&expected, &actual))
+        },
+    })
+}
+
+// The transcript the annotations describe: printed lines, then the errors in the same form as
+// `LoxError::brief`.
+fn expectations(source: &str) -> Vec<String> {
+    let mut output = vec![];
+    let mut errors = vec![];
+    let mut in_string = false;
+    for (index, line) in source.lines().enumerate() {
+        let comment = match comment_start(line, &mut in_string) {
+            Some(start) => line[start + 2..].trim(),
+            None => continue,
+        };
+        if let Some(text) = comment.strip_prefix("expect: ") {
+            output.push(text.to_string());
+        } else if let Some(message) = comment.strip_prefix("expect runtime error: ") {
+            errors.push(message.to_string());
+            errors.push(format!("[line {}]", index + 1));
+        } else if comment.starts_with("[line ") && comment.contains("] Error") {
+            errors.push(comment.to_string());
+        } else if comment.starts_with("Error") {
+            errors.push(format!("[line {}] {}", index + 1, comment));
+        }
+    }
+    output.extend(errors);
+    output
+}
+
+// Where the comment on `line` starts, if it has one: a `//` inside a string literal is part of
+// the string. `in_string` carries a string left open at the end of a line over to the next one.
+fn comment_start(line: &str, in_string: &mut bool) -> Option<usize> {
+    for (index, character) in line.char_indices() {
+        match character {
+            '"' => *in_string = !*in_string,
+            '/' if !*in_string && line[index + 1..].starts_with('/') => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+// A line diff from the longest common subsequence: `-` for expected lines that are missing and
+// `+` for actual lines that were not expected.
+fn diff(expected: &[String], actual: &[String]) -> String {
+    let (rows, columns) = (expected.len(), actual.len());
+    let mut common = vec![vec![0; columns + 1]; rows + 1];
+    for row in (0..rows).rev() {
+        for column in (0..columns).rev() {
+            common[row][column] = if expected[row] == actual[column] {
+                common[row + 1][column + 1] + 1
+            } else {
+                common[row + 1][column].max(common[row][column + 1])
+            };
+        }
+    }
+
+    let mut lines = vec![];
+    let (mut row, mut column) = (0, 0);
+    while row < rows || column < columns {
+        if row < rows && column < columns && expected[row] == actual[column] {
+            lines.push(format!("  {}", expected[row]));
+            row += 1;
+            column += 1;
+        } else if row < rows
+            && (column == columns || common[row + 1][column] >= common[row][column + 1])
+        {
+            lines.push(format!("- {}", expected[row]));
+            row += 1;
+        } else {
+            lines.push(format!("+ {}", actual[column]));
+            column += 1;
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::conformance::{diff, expectations};
+
+    #[test]
+    fn read_expectations() {
+        let source = "\
+print 1; // expect: 1
+// A comment that expects nothing.
+var a = \"x\" + 1; // expect runtime error: Operands must be two numbers or two strings.
+print this; // Error at 'this': Can't use 'this' outside of a class.
+// [line 9] Error at end: Expect '}' after block.
+print \"http://x\"; // expect: http://x
+print \"a
+// b\"; // expect: // b
+{";
+        assert_eq!(
+            vec![
+                "1",
+                "http://x",
+                "// b",
+                "Operands must be two numbers or two strings.",
+                "[line 3]",
+                "[line 4] Error at 'this': Can't use 'this' outside of a class.",
+                "[line 9] Error at end: Expect '}' after block.",
+            ],
+            expectations(source)
+        );
+    }
+
+    #[test]
+    fn diff_lines() {
+        let lines = |text: &str| text.split(' ').map(str::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            "  a\n- b\n+ x\n  c\n+ d",
+            diff(&lines("a b c"), &lines("a x c d"))
+        );
+    }
+}
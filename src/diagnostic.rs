@@ -0,0 +1,620 @@
+use crate::errors::{LoxError, Severity};
+use crate::span::{Position, SourceMap, Span};
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+// Spans covering more lines than this only show their first two and last two lines.
+const MAX_MULTILINE_LINES: usize = 4;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Style {
+    Plain,
+    Emphasis,
+    Primary,
+    Secondary,
+    Gutter,
+}
+
+/// A span to underline, with its columns resolved to 1-based lines and characters. `end_column`
+/// is exclusive and always lies on `end_line`.
+struct Annotation<'e> {
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+    primary: bool,
+    message: Option<&'e str>,
+}
+
+impl Annotation<'_> {
+    fn is_multiline(&self) -> bool {
+        self.end_line > self.start_line
+    }
+
+    fn style(&self) -> Style {
+        if self.primary {
+            Style::Primary
+        } else {
+            Style::Secondary
+        }
+    }
+
+    fn marker(&self) -> char {
+        if self.primary {
+            '^'
+        } else {
+            '-'
+        }
+    }
+
+    // Whether the vertical bar of a multi-line annotation is drawn next to `line`.
+    fn is_open_at(&self, line: usize) -> bool {
+        self.start_line < line && line <= self.end_line
+    }
+}
+
+/// The part of an output row to the right of the line number gutter, one styled cell per column.
+#[derive(Default)]
+struct Row(Vec<(char, Style)>);
+
+impl Row {
+    fn put(&mut self, column: usize, character: char, style: Style) {
+        if self.0.len() <= column {
+            self.0.resize(column + 1, (' ', Style::Plain));
+        }
+        self.0[column] = (character, style);
+    }
+
+    fn put_str(&mut self, column: usize, text: &str, style: Style) {
+        for (offset, character) in text.chars().enumerate() {
+            self.put(column + offset, character, style);
+        }
+    }
+
+    fn push_str(&mut self, text: &str, style: Style) {
+        self.0
+            .extend(text.chars().map(|character| (character, style)));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    Human,
+    Json,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<ErrorFormat, String> {
+        match format {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(format!(
+                "Unknown error format '{}', expected 'human' or 'json'.",
+                format
+            )),
+        }
+    }
+}
+
+/// Renders diagnostics of one source file, either the way rustc does (a header with the error
+/// code, the location, then the offending source lines with the primary span underlined by `^`
+/// and labels underlined by `-`) or as one JSON object per diagnostic for tools.
+pub struct Renderer {
+    path: String,
+    source: SourceMap,
+    colour: bool,
+    format: ErrorFormat,
+}
+
+impl Renderer {
+    pub fn new(path: &str, source: &str) -> Renderer {
+        Renderer {
+            path: path.to_string(),
+            source: SourceMap::new(source),
+            colour: false,
+            format: ErrorFormat::Human,
+        }
+    }
+
+    /// Only applies to the human format.
+    pub fn with_colour(mut self, colour: bool) -> Renderer {
+        self.colour = colour;
+        self
+    }
+
+    pub fn with_format(mut self, format: ErrorFormat) -> Renderer {
+        self.format = format;
+        self
+    }
+
+    /// Human diagnostics end with an empty line separating them from the next one, while JSON
+    /// diagnostics are a single line without its terminator.
+    pub fn render(&self, error: &LoxError) -> String {
+        match self.format {
+            ErrorFormat::Human => self.render_human(error),
+            ErrorFormat::Json => self.render_json(error),
+        }
+    }
+
+    // {"code":"E0101","severity":"error","message":"...","file":"main.lox",
+    //  "spans":[{"primary":true,"label":null,"start":{...},"end":{...}}],
+    //  "notes":[{"level":"help","message":"..."}]}
+    fn render_json(&self, error: &LoxError) -> String {
+        let mut spans = vec![json_span(&error.span, true, None)];
+        for label in error.labels.iter() {
+            spans.push(json_span(&label.span, false, Some(&label.message)));
+        }
+        let notes = error
+            .help
+            .iter()
+            .map(|help| format!("{{\"level\":\"help\",\"message\":{}}}", json_string(help)))
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"code\":{},\"severity\":{},\"message\":{},\"file\":{},\"spans\":[{}],\"notes\":[{}]}}",
+            json_string(error.code.code()),
+            json_string(&error.severity.to_string()),
+            json_string(&error.message),
+            json_string(&self.path),
+            spans.join(","),
+            notes.join(",")
+        )
+    }
+
+    fn render_human(&self, error: &LoxError) -> String {
+        let mut annotations = vec![self.annotation(&error.span, true, None)];
+        for label in error.labels.iter() {
+            annotations.push(self.annotation(&label.span, false, Some(&label.message)));
+        }
+
+        let mut lines = BTreeSet::new();
+        for annotation in annotations.iter() {
+            lines.insert(annotation.start_line);
+            lines.insert(annotation.end_line);
+            if annotation.end_line - annotation.start_line < MAX_MULTILINE_LINES {
+                lines.extend(annotation.start_line..annotation.end_line);
+            } else {
+                lines.insert(annotation.start_line + 1);
+                lines.insert(annotation.end_line - 1);
+            }
+        }
+        let width = lines.iter().last().map_or(1, |line| line.to_string().len());
+        let multiline = annotations
+            .iter()
+            .filter(|annotation| annotation.is_multiline())
+            .collect::<Vec<_>>();
+        // One column per multi-line annotation, plus one separating them from the source text.
+        let gutter = if multiline.is_empty() {
+            0
+        } else {
+            multiline.len() + 1
+        };
+
+        let severity = error.severity;
+        let mut output = self.paint(
+            &format!("{}[{}]", severity, error.code),
+            Style::Primary,
+            severity,
+        );
+        output.push_str(&self.paint(&format!(": {}", error.message), Style::Emphasis, severity));
+        output.push('\n');
+        output.push_str(&format!(
+            "{}{} {}:{}:{}\n",
+            " ".repeat(width),
+            self.paint("-->", Style::Gutter, severity),
+            self.path,
+            error.span.start.line,
+            error.span.start.column
+        ));
+        output.push_str(&self.line_row(width, None, &Row::default(), severity));
+
+        let mut previous = None;
+        for &line in lines.iter() {
+            if previous.is_some_and(|previous| line > previous + 1) {
+                let row = self.open_bars(&multiline, line, usize::MAX);
+                output.push_str(&self.paint("...", Style::Gutter, severity));
+                output.push_str(&self.serialize(&row, severity));
+                output.push('\n');
+            }
+            previous = Some(line);
+
+            let mut row = self.open_bars(&multiline, line, usize::MAX);
+            let text = self.source.line(line).unwrap_or("");
+            for (column, character) in text.chars().enumerate() {
+                row.put(gutter + column, character, Style::Plain);
+            }
+            output.push_str(&self.line_row(width, Some(line), &row, severity));
+
+            let mut rows = vec![];
+            let singles = annotations
+                .iter()
+                .filter(|annotation| !annotation.is_multiline() && annotation.start_line == line)
+                .collect::<Vec<_>>();
+            if !singles.is_empty() {
+                rows.extend(self.underline_rows(&singles, &multiline, gutter, line));
+            }
+            for (index, annotation) in multiline.iter().enumerate() {
+                if annotation.start_line == line {
+                    let mut row = self.open_bars(&multiline, line, index);
+                    let end = gutter + annotation.start_column - 1;
+                    for column in index + 1..end {
+                        row.put(column, '_', annotation.style());
+                    }
+                    row.put(end, annotation.marker(), annotation.style());
+                    rows.push(row);
+                }
+            }
+            for (index, annotation) in multiline.iter().enumerate() {
+                if annotation.end_line == line {
+                    let mut row = self.open_bars(&multiline, line, index);
+                    row.put(index, '|', annotation.style());
+                    let end = gutter + annotation.end_column.max(2) - 2;
+                    for column in index + 1..end {
+                        row.put(column, '_', annotation.style());
+                    }
+                    row.put(end, annotation.marker(), annotation.style());
+                    if let Some(message) = annotation.message {
+                        row.push_str(&format!(" {}", message), annotation.style());
+                    }
+                    rows.push(row);
+                }
+            }
+            for row in rows.iter() {
+                output.push_str(&self.line_row(width, None, row, severity));
+            }
+        }
+
+        if let Some(help) = &error.help {
+            output.push_str(&self.line_row(width, None, &Row::default(), severity));
+            output.push_str(&format!(
+                "{} {} {} {}\n",
+                " ".repeat(width),
+                self.paint("=", Style::Gutter, severity),
+                self.paint("help:", Style::Emphasis, severity),
+                help
+            ));
+        }
+        output
+    }
+
+    fn annotation<'e>(
+        &self,
+        span: &Span,
+        primary: bool,
+        message: Option<&'e str>,
+    ) -> Annotation<'e> {
+        let (mut end_line, mut end_column) = (span.end.line, span.end.column);
+        // A span ending right after a newline is drawn up to the end of the line it leaves.
+        if end_line > span.start.line && end_column == 1 {
+            end_line -= 1;
+            end_column = self
+                .source
+                .line(end_line)
+                .map_or(1, |text| text.chars().count() + 1);
+        }
+        Annotation {
+            start_line: span.start.line,
+            start_column: span.start.column,
+            end_line,
+            end_column,
+            primary,
+            message,
+        }
+    }
+
+    // A row holding the bars of the multi-line annotations still open at `line`, except `skip`.
+    fn open_bars(&self, multiline: &[&Annotation], line: usize, skip: usize) -> Row {
+        let mut row = Row::default();
+        for (index, annotation) in multiline.iter().enumerate() {
+            if index != skip && annotation.is_open_at(line) {
+                row.put(index, '|', annotation.style());
+            }
+        }
+        row
+    }
+
+    // Underlines every single-line annotation of a line on one row. The message of the rightmost
+    // one follows its underline; the others hang below, linked to their underline by `|`.
+    fn underline_rows(
+        &self,
+        singles: &[&Annotation],
+        multiline: &[&Annotation],
+        gutter: usize,
+        line: usize,
+    ) -> Vec<Row> {
+        let mut underline = self.open_bars(multiline, line, usize::MAX);
+        for annotation in singles.iter().filter(|annotation| !annotation.primary) {
+            self.underline(&mut underline, annotation, gutter);
+        }
+        // Drawn last so that the primary span stays visible where it overlaps a label.
+        for annotation in singles.iter().filter(|annotation| annotation.primary) {
+            self.underline(&mut underline, annotation, gutter);
+        }
+
+        let mut labelled = singles
+            .iter()
+            .filter(|annotation| annotation.message.is_some())
+            .collect::<Vec<_>>();
+        labelled.sort_by_key(|annotation| annotation.start_column);
+        let rightmost = singles.iter().map(|annotation| annotation.end_column).max();
+        if let Some(last) = labelled.last() {
+            if Some(last.end_column) == rightmost {
+                let message = format!(" {}", last.message.unwrap_or(""));
+                underline.push_str(&message, last.style());
+                labelled.pop();
+            }
+        }
+
+        let mut rows = vec![underline];
+        if labelled.is_empty() {
+            return rows;
+        }
+        let column = |annotation: &Annotation| gutter + annotation.start_column - 1;
+        let mut connector = self.open_bars(multiline, line, usize::MAX);
+        for annotation in labelled.iter() {
+            connector.put(column(annotation), '|', annotation.style());
+        }
+        rows.push(connector);
+        while let Some(annotation) = labelled.pop() {
+            let mut row = self.open_bars(multiline, line, usize::MAX);
+            for pending in labelled.iter() {
+                row.put(column(pending), '|', pending.style());
+            }
+            row.put_str(
+                column(annotation),
+                annotation.message.unwrap_or(""),
+                annotation.style(),
+            );
+            rows.push(row);
+        }
+        rows
+    }
+
+    fn underline(&self, row: &mut Row, annotation: &Annotation, gutter: usize) {
+        let length = annotation
+            .end_column
+            .saturating_sub(annotation.start_column)
+            .max(1);
+        let start = gutter + annotation.start_column - 1;
+        for column in start..start + length {
+            row.put(column, annotation.marker(), annotation.style());
+        }
+    }
+
+    fn line_row(&self, width: usize, line: Option<usize>, row: &Row, severity: Severity) -> String {
+        let number = line.map_or(String::new(), |line| line.to_string());
+        let content = self.serialize(row, severity);
+        let prefix = if content.is_empty() {
+            format!("{:>width$} |", number, width = width)
+        } else {
+            format!("{:>width$} | ", number, width = width)
+        };
+        format!(
+            "{}{}\n",
+            self.paint(&prefix, Style::Gutter, severity),
+            content
+        )
+    }
+
+    fn serialize(&self, row: &Row, severity: Severity) -> String {
+        let cells = match row.0.iter().rposition(|(character, _)| *character != ' ') {
+            Some(last) => &row.0[..=last],
+            None => &[],
+        };
+        let mut output = String::new();
+        let mut run = String::new();
+        let mut run_style = Style::Plain;
+        for &(character, style) in cells {
+            if style != run_style && !run.is_empty() {
+                output.push_str(&self.paint(&run, run_style, severity));
+                run.clear();
+            }
+            run_style = style;
+            run.push(character);
+        }
+        output.push_str(&self.paint(&run, run_style, severity));
+        output
+    }
+
+    fn paint(&self, text: &str, style: Style, severity: Severity) -> String {
+        let code = match style {
+            Style::Plain => return text.to_string(),
+            Style::Emphasis => "1",
+            Style::Primary => match severity {
+                Severity::Error => "1;31",
+                Severity::Warning => "1;33",
+                Severity::Note => "1;32",
+            },
+            Style::Secondary | Style::Gutter => "1;34",
+        };
+        if !self.colour || text.is_empty() {
+            return text.to_string();
+        }
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    }
+}
+
+fn json_span(span: &Span, primary: bool, label: Option<&str>) -> String {
+    format!(
+        "{{\"primary\":{},\"label\":{},\"start\":{},\"end\":{}}}",
+        primary,
+        label.map_or("null".to_string(), json_string),
+        json_position(&span.start),
+        json_position(&span.end)
+    )
+}
+
+fn json_position(position: &Position) -> String {
+    format!(
+        "{{\"offset\":{},\"line\":{},\"column\":{}}}",
+        position.offset, position.line, position.column
+    )
+}
+
+fn json_string(text: &str) -> String {
+    let mut output = String::from("\"");
+    for character in text.chars() {
+        match character {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            control if control.is_control() => {
+                output.push_str(&format!("\\u{:04x}", control as u32))
+            }
+            _ => output.push(character),
+        }
+    }
+    output.push('"');
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::{json_string, ErrorFormat, Renderer};
+    use crate::errors::{ErrorCode, LoxError};
+    use crate::span::SourceMap;
+
+    #[test]
+    fn render_single_line() {
+        let source = "var a = 1;\nprint (a + 2;\n";
+        let map = SourceMap::new(source);
+        let error = LoxError::new(
+            ErrorCode::ExpectedToken,
+            "Expect ')' after expression.",
+            map.span(23, 24),
+        )
+        .with_help("Close the group with ')'.");
+        let expected = "\
+error[E0101]: Expect ')' after expression.
+ --> main.lox:2:13
+  |
+2 | print (a + 2;
+  |             ^
+  |
+  = help: Close the group with ')'.
+";
+        assert_eq!(expected, Renderer::new("main.lox", source).render(&error));
+    }
+
+    #[test]
+    fn render_labels_on_one_line() {
+        let source = "print first + second + third;";
+        let map = SourceMap::new(source);
+        let error = LoxError::new(
+            ErrorCode::InvalidOperand,
+            "Operands must be numbers.",
+            map.span(12, 13),
+        )
+        .with_label(map.span(6, 11), "a string")
+        .with_label(map.span(14, 20), "a number")
+        .with_label(map.span(23, 28), "a function");
+        let expected = "\
+error[E0300]: Operands must be numbers.
+ --> main.lox:1:13
+  |
+1 | print first + second + third;
+  |       ----- ^ ------   ----- a function
+  |       |       |
+  |       |       a number
+  |       a string
+";
+        assert_eq!(expected, Renderer::new("main.lox", source).render(&error));
+    }
+
+    #[test]
+    fn render_multiline_span() {
+        let source = "print 1;\nvar s = \"abc\ndef\nghi;\n";
+        let map = SourceMap::new(source);
+        let error = LoxError::new(
+            ErrorCode::UnterminatedString,
+            "Unterminated string.",
+            map.span(17, 30),
+        )
+        .with_label(map.span(9, 12), "in this declaration");
+        let expected = "\
+error[E0001]: Unterminated string.
+ --> main.lox:2:9
+  |
+2 |   var s = \"abc
+  |   --- in this declaration
+  |  _________^
+3 | | def
+4 | | ghi;
+  | |____^
+";
+        assert_eq!(expected, Renderer::new("main.lox", source).render(&error));
+    }
+
+    #[test]
+    fn render_distant_lines() {
+        let source = "var a = 1;\n\n\n\nvar a = 2;";
+        let map = SourceMap::new(source);
+        let error = LoxError::new(
+            ErrorCode::AlreadyDeclared,
+            "Already declared.",
+            map.span(18, 19),
+        )
+        .with_label(map.span(4, 5), "first declared here");
+        let expected = "\
+error[E0201]: Already declared.
+ --> main.lox:5:5
+  |
+1 | var a = 1;
+  |     - first declared here
+...
+5 | var a = 2;
+  |     ^
+";
+        assert_eq!(expected, Renderer::new("main.lox", source).render(&error));
+    }
+
+    #[test]
+    fn render_colour() {
+        let source = "1 +;";
+        let error = LoxError::new(
+            ErrorCode::ExpectedExpression,
+            "Expect expression.",
+            SourceMap::new(source).span(3, 4),
+        );
+        let rendered = Renderer::new("main.lox", source)
+            .with_colour(true)
+            .render(&error);
+        assert!(rendered
+            .starts_with("\x1b[1;31merror[E0100]\x1b[0m\x1b[1m: Expect expression.\x1b[0m\n"));
+        assert!(rendered.contains("\x1b[1;34m  | \x1b[0m   \x1b[1;31m^\x1b[0m\n"));
+    }
+
+    #[test]
+    fn render_json() {
+        let source = "var s = \"a\";\nprint s s;";
+        let map = SourceMap::new(source);
+        let error = LoxError::new(
+            ErrorCode::ExpectedToken,
+            "Expect ';' after value.",
+            map.span(21, 22),
+        )
+        .with_label(map.span(13, 18), "in this \"print\"")
+        .with_help("Add a ';'.");
+        let expected = concat!(
+            r#"{"code":"E0101","severity":"error","message":"Expect ';' after value.","file":"dir\\main.lox","#,
+            r#""spans":[{"primary":true,"label":null,"start":{"offset":21,"line":2,"column":9},"end":{"offset":22,"line":2,"column":10}},"#,
+            r#"{"primary":false,"label":"in this \"print\"","start":{"offset":13,"line":2,"column":1},"end":{"offset":18,"line":2,"column":6}}],"#,
+            r#""notes":[{"level":"help","message":"Add a ';'."}]}"#
+        );
+        let renderer = Renderer::new("dir\\main.lox", source).with_format(ErrorFormat::Json);
+        assert_eq!(expected, renderer.render(&error));
+    }
+
+    #[test]
+    fn json_escapes() {
+        assert_eq!(
+            r#""a\"b\\c\nd\te\u0001é""#,
+            json_string("a\"b\\c\nd\te\u{1}é")
+        );
+        assert_eq!(Ok(ErrorFormat::Json), "json".parse());
+        assert!("xml".parse::<ErrorFormat>().is_err());
+    }
+}
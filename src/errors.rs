@@ -1,4 +1,317 @@
+use crate::span::Span;
+use std::error::Error;
+use std::fmt;
+
+/// Which stage of running a program reported a diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Scan,
+    Parse,
+    Resolve,
+    /// Limits of the bytecode compiler, only reported when running on the virtual machine.
+    Compile,
+    Runtime,
+}
+
+/// Stable identifier of every diagnostic, so that tools and documentation can refer to an error
+/// without matching on its message. Codes are grouped by hundreds per phase and never reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    UnterminatedString,
+    UnexpectedCharacter,
+    InvalidNumber,
+
+    ExpectedExpression,
+    ExpectedToken,
+    InvalidAssignmentTarget,
+    TooManyArguments,
+    TooManyParameters,
+
+    ReadInOwnInitializer,
+    AlreadyDeclared,
+    TopLevelReturn,
+    ReturnFromInitializer,
+    ThisOutsideClass,
+    SuperOutsideClass,
+    SuperWithoutSuperclass,
+    InheritFromSelf,
+
+    InvalidOperand,
+    UndefinedVariable,
+    UndefinedProperty,
+    NotCallable,
+    ArityMismatch,
+    NotAnInstance,
+    SuperclassNotClass,
+    StackOverflow,
+    Internal,
+
+    TooManyConstants,
+    TooManyLocals,
+    JumpTooLarge,
+    TooManyUpvalues,
+}
+
+impl ErrorCode {
+    pub fn code(&self) -> &'static str {
+        use ErrorCode::*;
+        match self {
+            UnterminatedString => "E0001",
+            UnexpectedCharacter => "E0002",
+            InvalidNumber => "E0003",
+
+            ExpectedExpression => "E0100",
+            ExpectedToken => "E0101",
+            InvalidAssignmentTarget => "E0102",
+            TooManyArguments => "E0103",
+            TooManyParameters => "E0104",
+
+            ReadInOwnInitializer => "E0200",
+            AlreadyDeclared => "E0201",
+            TopLevelReturn => "E0202",
+            ReturnFromInitializer => "E0203",
+            ThisOutsideClass => "E0204",
+            SuperOutsideClass => "E0205",
+            SuperWithoutSuperclass => "E0206",
+            InheritFromSelf => "E0207",
+
+            InvalidOperand => "E0300",
+            UndefinedVariable => "E0301",
+            UndefinedProperty => "E0302",
+            NotCallable => "E0303",
+            ArityMismatch => "E0304",
+            NotAnInstance => "E0305",
+            SuperclassNotClass => "E0306",
+            StackOverflow => "E0307",
+            Internal => "E0399",
+
+            TooManyConstants => "E0400",
+            TooManyLocals => "E0401",
+            JumpTooLarge => "E0402",
+            TooManyUpvalues => "E0404",
+        }
+    }
+
+    pub fn phase(&self) -> Phase {
+        use ErrorCode::*;
+        match self {
+            UnterminatedString | UnexpectedCharacter | InvalidNumber => Phase::Scan,
+            ExpectedExpression
+            | ExpectedToken
+            | InvalidAssignmentTarget
+            | TooManyArguments
+            | TooManyParameters => Phase::Parse,
+            ReadInOwnInitializer
+            | AlreadyDeclared
+            | TopLevelReturn
+            | ReturnFromInitializer
+            | ThisOutsideClass
+            | SuperOutsideClass
+            | SuperWithoutSuperclass
+            | InheritFromSelf => Phase::Resolve,
+            TooManyConstants | TooManyLocals | JumpTooLarge | TooManyUpvalues => Phase::Compile,
+            InvalidOperand | UndefinedVariable | UndefinedProperty | NotCallable
+            | ArityMismatch | NotAnInstance | SuperclassNotClass | StackOverflow | Internal => {
+                Phase::Runtime
+            }
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+                Severity::Note => "note",
+            }
+        )
+    }
+}
+
+/// A secondary location that helps explain a diagnostic, such as an earlier declaration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+/// A diagnostic reported by any phase, from scanning to running the program.
+#[derive(Debug, Clone, PartialEq)]
 pub struct LoxError {
-    pub line: usize,
+    pub code: ErrorCode,
+    pub severity: Severity,
     pub message: String,
-}
\ No newline at end of file
+    pub span: Span,
+    pub labels: Vec<Label>,
+    pub help: Option<String>,
+    /// Name of the source that was running, when it was given one, e.g. a path.
+    pub file: Option<String>,
+}
+
+impl LoxError {
+    pub fn new(code: ErrorCode, message: impl Into<String>, span: Span) -> LoxError {
+        LoxError {
+            code,
+            severity: Severity::Error,
+            message: message.into(),
+            span,
+            labels: vec![],
+            help: None,
+            file: None,
+        }
+    }
+
+    pub fn with_severity(mut self, severity: Severity) -> LoxError {
+        self.severity = severity;
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> LoxError {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> LoxError {
+        self.help = Some(help.into());
+        self
+    }
+
+    pub fn with_file(mut self, file: impl Into<String>) -> LoxError {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// The line the primary span starts on.
+    pub fn line(&self) -> usize {
+        self.span.start.line
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.code.phase()
+    }
+
+    /// The terse report of the reference implementation, e.g. `[line 1] Error at ';': ...`.
+    /// `source` is the program the error was found in, to quote the offending lexeme.
+    pub fn brief(&self, source: &str) -> String {
+        match self.phase() {
+            Phase::Scan => format!("[line {}] Error: {}", self.line(), self.message),
+            Phase::Parse | Phase::Resolve | Phase::Compile => {
+                let lexeme = source
+                    .get(self.span.start.offset..self.span.end.offset)
+                    .unwrap_or("");
+                let location = if lexeme.is_empty() {
+                    "end".to_string()
+                } else {
+                    format!("'{}'", lexeme)
+                };
+                format!(
+                    "[line {}] Error at {}: {}",
+                    self.line(),
+                    location,
+                    self.message
+                )
+            }
+            Phase::Runtime => format!("{}\n[line {}]", self.message, self.line()),
+        }
+    }
+}
+
+impl fmt::Display for LoxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)
+    }
+}
+
+impl Error for LoxError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::{ErrorCode, LoxError, Phase, Severity};
+    use crate::span::SourceMap;
+
+    #[test]
+    fn error_display() {
+        let map = SourceMap::new("print 1");
+        let error = LoxError::new(
+            ErrorCode::ExpectedToken,
+            "Expect ';' after value.",
+            map.span(7, 7),
+        )
+        .with_label(map.span(0, 5), "statement starts here")
+        .with_help("Add a ';'.");
+        assert_eq!("error[E0101]: Expect ';' after value.", error.to_string());
+        assert_eq!(Phase::Parse, error.phase());
+        assert_eq!(1, error.labels.len());
+        assert_eq!(Some("Add a ';'.".to_string()), error.help);
+
+        let warning = error.with_severity(Severity::Warning);
+        assert_eq!(
+            "warning[E0101]: Expect ';' after value.",
+            warning.to_string()
+        );
+    }
+
+    #[test]
+    fn error_brief() {
+        let source = "var a = \"one\";\nprint a b";
+        let map = SourceMap::new(source);
+
+        let error = LoxError::new(
+            ErrorCode::UnexpectedCharacter,
+            "Unexpected character '@'.",
+            map.span(0, 1),
+        );
+        assert_eq!(
+            "[line 1] Error: Unexpected character '@'.",
+            error.brief(source)
+        );
+
+        let error = LoxError::new(
+            ErrorCode::ExpectedToken,
+            "Expect ';' after value.",
+            map.span(8, 13),
+        );
+        assert_eq!(
+            "[line 1] Error at '\"one\"': Expect ';' after value.",
+            error.brief(source)
+        );
+
+        let end = source.len();
+        let error = LoxError::new(
+            ErrorCode::ExpectedToken,
+            "Expect ';' after value.",
+            map.span(end, end),
+        );
+        assert_eq!(
+            "[line 2] Error at end: Expect ';' after value.",
+            error.brief(source)
+        );
+
+        let error = LoxError::new(
+            ErrorCode::UndefinedVariable,
+            "Undefined variable 'b'.",
+            map.span(23, 24),
+        );
+        assert_eq!("Undefined variable 'b'.\n[line 2]", error.brief(source));
+    }
+}
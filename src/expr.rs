@@ -1,21 +1,70 @@
-use crate::token::Token;
+use crate::span::Span;
+use crate::token::Item;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Identifies a single variable reference in the AST, so that the resolver can record how many
+/// scopes away its binding lives and the interpreter can look that distance up again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprId(usize);
+
+impl ExprId {
+    pub fn next() -> ExprId {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        ExprId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// An expression node together with the source it was parsed from.
 #[derive(Debug, PartialEq)]
-pub enum Expr {
-    Unary(Token, Box<Expr>),
-    Binary(Box<Expr>, Token, Box<Expr>),
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Expr {
+        Expr { kind, span }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ExprKind {
+    Unary(Item, Box<Expr>),
+    Binary(Box<Expr>, Item, Box<Expr>),
     Grouping(Box<Expr>),
-    Literal(Token),
+    Literal(Item),
+    Logical(Box<Expr>, Item, Box<Expr>),
+    Variable(ExprId, Item),
+    Assign(ExprId, Item, Box<Expr>),
+    Call(Box<Expr>, Item, Vec<Expr>),
+    Get(Box<Expr>, Item),
+    Set(Box<Expr>, Item, Box<Expr>),
+    This(ExprId, Item),
+    Super(ExprId, Item, Item),
 }
 
-impl ToString for Expr {
-    fn to_string(&self) -> String {
-        use Expr::*;
-        String::from(match self {
-            Unary(_, _) => "Unary",
-            Binary(_, _, _) => "Binary",
-            Grouping(_) => "Grouping",
-            Literal(_) => "Literal",
-        })
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ExprKind::*;
+        write!(
+            f,
+            "{}",
+            match self.kind {
+                Unary(_, _) => "Unary",
+                Binary(_, _, _) => "Binary",
+                Grouping(_) => "Grouping",
+                Literal(_) => "Literal",
+                Logical(_, _, _) => "Logical",
+                Variable(_, _) => "Variable",
+                Assign(_, _, _) => "Assign",
+                Call(_, _, _) => "Call",
+                Get(_, _) => "Get",
+                Set(_, _, _) => "Set",
+                This(_, _) => "This",
+                Super(_, _, _) => "Super",
+            }
+        )
     }
 }
 
@@ -23,31 +72,47 @@ pub trait Visitor<T> {
     fn new() -> Self;
 
     fn visit_expr(&mut self, expr: &Expr) -> T {
-        use Expr::*;
+        use ExprKind::*;
 
-        match expr {
-            Binary(left, token, right) => self.visit_expr_binary(left, token, right),
-            Unary(token, expression) => self.visit_expr_unary(token, expression),
+        match &expr.kind {
+            Binary(left, operator, right) => self.visit_expr_binary(left, operator, right),
+            Unary(operator, expression) => self.visit_expr_unary(operator, expression),
             Grouping(expression) => self.visit_expr_grouping(expression),
-            Literal(token) => self.visit_expr_literal(token),
+            Literal(value) => self.visit_expr_literal(value),
+            Logical(left, operator, right) => self.visit_expr_logical(left, operator, right),
+            Variable(id, name) => self.visit_expr_variable(*id, name),
+            Assign(id, name, value) => self.visit_expr_assign(*id, name, value),
+            Call(callee, paren, arguments) => self.visit_expr_call(callee, paren, arguments),
+            Get(object, name) => self.visit_expr_get(object, name),
+            Set(object, name, value) => self.visit_expr_set(object, name, value),
+            This(id, keyword) => self.visit_expr_this(*id, keyword),
+            Super(id, keyword, method) => self.visit_expr_super(*id, keyword, method),
         }
     }
 
-    fn visit_expr_unary(&mut self, _token: &Token, _expr: &Expr) -> T {
-        unimplemented!()
-    }
+    fn visit_expr_unary(&mut self, operator: &Item, expr: &Expr) -> T;
 
-    fn visit_expr_binary(&mut self, _left: &Expr, _token: &Token, _right: &Expr) -> T {
-        unimplemented!()
-    }
+    fn visit_expr_binary(&mut self, left: &Expr, operator: &Item, right: &Expr) -> T;
 
-    fn visit_expr_grouping(&mut self, _expr: &Expr) -> T {
-        unimplemented!()
-    }
+    fn visit_expr_grouping(&mut self, expr: &Expr) -> T;
 
-    fn visit_expr_literal(&mut self, _token: &Token) -> T {
-        unimplemented!()
-    }
+    fn visit_expr_literal(&mut self, value: &Item) -> T;
+
+    fn visit_expr_logical(&mut self, left: &Expr, operator: &Item, right: &Expr) -> T;
+
+    fn visit_expr_variable(&mut self, id: ExprId, name: &Item) -> T;
+
+    fn visit_expr_assign(&mut self, id: ExprId, name: &Item, value: &Expr) -> T;
+
+    fn visit_expr_call(&mut self, callee: &Expr, paren: &Item, arguments: &[Expr]) -> T;
+
+    fn visit_expr_get(&mut self, object: &Expr, name: &Item) -> T;
+
+    fn visit_expr_set(&mut self, object: &Expr, name: &Item, value: &Expr) -> T;
+
+    fn visit_expr_this(&mut self, id: ExprId, keyword: &Item) -> T;
+
+    fn visit_expr_super(&mut self, id: ExprId, keyword: &Item, method: &Item) -> T;
 }
 
 pub struct VisitorPrinter {}
@@ -57,7 +122,7 @@ impl VisitorPrinter {
         self.visit_expr(expr)
     }
 
-    fn parenthesize(&mut self, output: String) -> String {
+    pub(crate) fn parenthesize(&mut self, output: String) -> String {
         format!("({})", output)
     }
 }
@@ -67,34 +132,68 @@ impl Visitor<String> for VisitorPrinter {
         VisitorPrinter {}
     }
 
-    fn visit_expr_binary(
-        &mut self,
-        left: &Expr,
-        token: &Token,
-        right: &Expr,
-    ) -> String {
-        let left_print = self.visit_expr(&left);
-        let right_print = self.visit_expr(&right);
-        self.parenthesize(format!(
-            "{} {} {}",
-            token.to_string(),
-            left_print,
-            right_print
-        ))
+    fn visit_expr_binary(&mut self, left: &Expr, operator: &Item, right: &Expr) -> String {
+        let left_print = self.visit_expr(left);
+        let right_print = self.visit_expr(right);
+        self.parenthesize(format!("{} {} {}", operator.token, left_print, right_print))
     }
 
     fn visit_expr_grouping(&mut self, expr: &Expr) -> String {
-        let printed_expr = self.visit_expr(&expr);
+        let printed_expr = self.visit_expr(expr);
         self.parenthesize(format!("group {}", printed_expr))
     }
 
-    fn visit_expr_literal(&mut self, token: &Token) -> String {
-        format!("{}", token.to_string())
+    fn visit_expr_literal(&mut self, value: &Item) -> String {
+        value.token.to_string()
+    }
+
+    fn visit_expr_unary(&mut self, operator: &Item, expr: &Expr) -> String {
+        let printed_expr = self.visit_expr(expr);
+        self.parenthesize(format!("{} {}", operator.token, printed_expr))
+    }
+
+    fn visit_expr_logical(&mut self, left: &Expr, operator: &Item, right: &Expr) -> String {
+        self.visit_expr_binary(left, operator, right)
+    }
+
+    fn visit_expr_variable(&mut self, _id: ExprId, name: &Item) -> String {
+        name.lexeme.clone()
+    }
+
+    fn visit_expr_assign(&mut self, _id: ExprId, name: &Item, value: &Expr) -> String {
+        let printed_value = self.visit_expr(value);
+        self.parenthesize(format!("= {} {}", name.lexeme, printed_value))
+    }
+
+    fn visit_expr_call(&mut self, callee: &Expr, _paren: &Item, arguments: &[Expr]) -> String {
+        let mut output = format!("call {}", self.visit_expr(callee));
+        for argument in arguments {
+            output.push(' ');
+            output.push_str(&self.visit_expr(argument));
+        }
+        self.parenthesize(output)
+    }
+
+    fn visit_expr_get(&mut self, object: &Expr, name: &Item) -> String {
+        let printed_object = self.visit_expr(object);
+        self.parenthesize(format!(". {} {}", printed_object, name.lexeme))
+    }
+
+    fn visit_expr_set(&mut self, object: &Expr, name: &Item, value: &Expr) -> String {
+        let printed_object = self.visit_expr(object);
+        let printed_value = self.visit_expr(value);
+        self.parenthesize(format!(
+            "= {} {} {}",
+            printed_object, name.lexeme, printed_value
+        ))
+    }
+
+    fn visit_expr_this(&mut self, _id: ExprId, _keyword: &Item) -> String {
+        "this".to_string()
     }
 
-    fn visit_expr_unary(&mut self, token: &Token, expr: &Expr) -> String {
-        let printed_expr = self.visit_expr(&expr);
-        self.parenthesize(format!("{} {}", token.to_string(), printed_expr))
+    fn visit_expr_super(&mut self, _id: ExprId, _keyword: &Item, method: &Item) -> String {
+        self.parenthesize(format!("super {}", method.lexeme))
     }
 }
 
@@ -102,16 +201,22 @@ impl Visitor<String> for VisitorPrinter {
 mod tests {
     #[test]
     fn print_visit() {
-        use crate::expr::{Expr, VisitorPrinter};
-        use crate::token::Token;
-        use Expr::*;
+        use crate::expr::{Expr, ExprKind, VisitorPrinter};
+        use crate::span::Span;
+        use crate::token::{Item, Token};
+        use ExprKind::*;
+        let item = |lexeme: &str, token| Item::new(lexeme.to_string(), token, Span::default());
+        let node = |kind| Box::new(Expr::new(kind, Span::default()));
         let mut visitor = VisitorPrinter {};
-        let node = Binary(
-            Box::new(Unary(Token::Minus, Box::new(Literal(Token::Number(123.0))))),
-            Token::Star,
-            Box::new(Grouping(Box::new(Literal(Token::Number(45.67))))),
-        );
-        let string_ast = VisitorPrinter::print(&mut visitor, &node);
+        let expr = node(Binary(
+            node(Unary(
+                item("-", Token::Minus),
+                node(Literal(item("123", Token::Number(123.0)))),
+            )),
+            item("*", Token::Star),
+            node(Grouping(node(Literal(item("45.67", Token::Number(45.67)))))),
+        ));
+        let string_ast = VisitorPrinter::print(&mut visitor, &expr);
 
         assert_eq!("(* (- 123) (group 45.67))", string_ast);
     }
@@ -0,0 +1,249 @@
+use crate::errors::LoxError;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::token::{Item, Token};
+
+const INDENT: &str = "    ";
+
+/// Lays `source` out canonically: one statement per line, blocks indented by four spaces,
+/// single spaces around operators, and at most one blank line in a row. Comments are kept.
+/// Sources that do not parse are left alone and their errors returned.
+pub fn format_source(source: &str) -> Result<String, Vec<LoxError>> {
+    let items = Scanner::new(source).scan_tokens()?;
+    Parser::new(items.clone()).parse()?;
+
+    let mut formatter = Formatter::new();
+    let mut end = 0;
+    for (index, item) in items.iter().enumerate() {
+        let closing = matches!(item.token, Token::RightBrace | Token::Eof);
+        formatter.gap(&source[end..item.span.start.offset], closing);
+        if item.token == Token::Eof {
+            break;
+        }
+        let previous = index.checked_sub(1).map(|index| &items[index]);
+        formatter.item(item, previous, &items[index + 1].token);
+        end = item.span.end.offset;
+    }
+    Ok(formatter.finish())
+}
+
+struct Formatter {
+    output: String,
+    line: String,
+    indent: usize,
+    parens: usize,
+    // The last item written was `!` or a negation, which stick to their operand.
+    after_unary: bool,
+    // Set after a statement or a brace, so that a comment on the same line can still follow.
+    break_pending: bool,
+}
+
+impl Formatter {
+    fn new() -> Formatter {
+        Formatter {
+            output: String::new(),
+            line: String::new(),
+            indent: 0,
+            parens: 0,
+            after_unary: false,
+            break_pending: false,
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.newline();
+        self.output
+    }
+
+    fn newline(&mut self) {
+        if !self.line.is_empty() {
+            self.output.push_str(&self.line);
+            self.output.push('\n');
+            self.line.clear();
+        }
+        self.break_pending = false;
+    }
+
+    // Blank lines separate statements, so there is none at the start of the file or a block.
+    fn blank_line(&mut self) {
+        self.newline();
+        if !self.output.is_empty()
+            && !self.output.ends_with("{\n")
+            && !self.output.ends_with("\n\n")
+        {
+            self.output.push('\n');
+        }
+    }
+
+    fn write(&mut self, text: &str, space: bool) {
+        if self.line.is_empty() {
+            self.line.push_str(&INDENT.repeat(self.indent));
+        } else if space {
+            self.line.push(' ');
+        }
+        self.line.push_str(text);
+    }
+
+    /// Whitespace and comments between two items, `closing` when the second one ends a block or
+    /// the file and a blank line would only pad it.
+    fn gap(&mut self, text: &str, closing: bool) {
+        let mut newlines = 0;
+        let mut rest = text;
+        while let Some(start) = rest.find("//") {
+            newlines += rest[..start].matches('\n').count();
+            let length = rest[start..].find('\n').unwrap_or(rest.len() - start);
+            let comment = rest[start..start + length].trim_end();
+            if newlines == 0 && !self.line.is_empty() {
+                self.line.push(' ');
+                self.line.push_str(comment);
+            } else {
+                if newlines > 1 {
+                    self.blank_line();
+                }
+                self.newline();
+                self.write(comment, false);
+            }
+            self.newline();
+            newlines = 0;
+            rest = &rest[start + length..];
+        }
+        if newlines + rest.matches('\n').count() > 1 && !closing {
+            self.blank_line();
+        }
+    }
+
+    fn item(&mut self, item: &Item, previous: Option<&Item>, next: &Token) {
+        if self.break_pending {
+            self.newline();
+        }
+        if item.token == Token::RightBrace {
+            self.indent = self.indent.saturating_sub(1);
+            if !self.line.ends_with('{') {
+                self.newline();
+            }
+        }
+        self.write(&item.lexeme, spaced(previous, item) && !self.after_unary);
+        self.after_unary = match item.token {
+            Token::Bang => true,
+            Token::Minus => !ends_operand(previous),
+            _ => false,
+        };
+
+        match item.token {
+            Token::LeftParen => self.parens += 1,
+            Token::RightParen => self.parens = self.parens.saturating_sub(1),
+            Token::SemiColon if self.parens == 0 => self.break_pending = true,
+            Token::LeftBrace => {
+                self.indent += 1;
+                self.break_pending = *next != Token::RightBrace;
+            }
+            Token::RightBrace => self.break_pending = *next != Token::Else,
+            _ => {}
+        }
+    }
+}
+
+// Whether a space separates `item` from the item before it on the same line.
+fn spaced(previous: Option<&Item>, item: &Item) -> bool {
+    match (previous.map(|previous| &previous.token), &item.token) {
+        (None, _) => false,
+        (_, Token::RightParen) | (_, Token::Comma) | (_, Token::SemiColon) | (_, Token::Dot) => {
+            false
+        }
+        (Some(Token::LeftParen), _)
+        | (Some(Token::Dot), _)
+        | (Some(Token::LeftBrace), Token::RightBrace) => false,
+        // Calls and declarations, as opposed to `if (` or `a + (`.
+        (_, Token::LeftParen) if ends_operand(previous) => false,
+        _ => true,
+    }
+}
+
+// Whether `previous` is the last item of an operand, in which case a `-` after it is a
+// subtraction rather than a negation, and a `(` after it starts a call.
+fn ends_operand(previous: Option<&Item>) -> bool {
+    previous.is_some_and(|previous| {
+        matches!(
+            previous.token,
+            Token::Identifier(_)
+                | Token::String(_)
+                | Token::Number(_)
+                | Token::RightParen
+                | Token::True
+                | Token::False
+                | Token::Nil
+                | Token::This
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::formatter::format_source;
+
+    #[test]
+    fn format_layout() {
+        let source = "var a=-1;fun add(a,b){return a+b;}\nif(!a) print add( a , -a ) ; else {print\n\"x\"; }\nclass B<A{init(){super.init();this.x=1;}} for(var i=0;i<3;i=i+1){}";
+        let expected = "\
+var a = -1;
+fun add(a, b) {
+    return a + b;
+}
+if (!a) print add(a, -a);
+else {
+    print \"x\";
+}
+class B < A {
+    init() {
+        super.init();
+        this.x = 1;
+    }
+}
+for (var i = 0; i < 3; i = i + 1) {}
+";
+        assert_eq!(Ok(expected.to_string()), format_source(source));
+        assert_eq!(Ok(expected.to_string()), format_source(expected));
+    }
+
+    #[test]
+    fn format_calls_on_any_operand() {
+        let source = "\"str\" ();true ();nil ();1 ();f () ();this ();super.m ();print (1);";
+        let expected = "\
+\"str\"();
+true();
+nil();
+1();
+f()();
+this();
+super.m();
+print (1);
+";
+        assert_eq!(Ok(expected.to_string()), format_source(source));
+    }
+
+    #[test]
+    fn format_keeps_comments_and_blank_lines() {
+        let source = "// header\n\n\n\nvar a = 1;   // one\n{\n\n  // inside\n  print a;\n\n}\n\n\nprint a - 1; // done\n";
+        let expected = "\
+// header
+
+var a = 1; // one
+{
+    // inside
+    print a;
+}
+
+print a - 1; // done
+";
+        assert_eq!(Ok(expected.to_string()), format_source(source));
+    }
+
+    #[test]
+    fn format_rejects_invalid_source() {
+        let errors = format_source("print 1").unwrap_err();
+        assert_eq!(
+            "[line 1] Error at end: Expect ';' after value.",
+            errors[0].brief("print 1")
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use crate::errors::{ErrorCode, LoxError};
+use crate::interpreter::function::Function;
+use crate::interpreter::{Callable, Interpreter, Value};
+use crate::token::Item;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+pub struct Class {
+    pub name: String,
+    pub superclass: Option<Rc<Class>>,
+    methods: HashMap<String, Rc<Function>>,
+}
+
+impl Class {
+    pub fn new(
+        name: String,
+        superclass: Option<Rc<Class>>,
+        methods: HashMap<String, Rc<Function>>,
+    ) -> Class {
+        Class {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    /// Looks the method up on this class first, then walks the superclass chain.
+    pub fn find_method(&self, name: &str) -> Option<Rc<Function>> {
+        match self.methods.get(name) {
+            Some(method) => Some(Rc::clone(method)),
+            None => self
+                .superclass
+                .as_ref()
+                .and_then(|superclass| superclass.find_method(name)),
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.find_method("init")
+            .map_or(0, |initializer| initializer.arity())
+    }
+
+    /// Creates a new instance and runs `init` on it when the class (or an ancestor) defines one.
+    pub fn instantiate(
+        class: &Rc<Class>,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, LoxError> {
+        let instance = Rc::new(RefCell::new(Instance::new(Rc::clone(class))));
+        if let Some(initializer) = class.find_method("init") {
+            initializer
+                .bind(Rc::clone(&instance))
+                .call(interpreter, arguments)?;
+        }
+        Ok(Value::Instance(instance))
+    }
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+pub struct Instance {
+    pub class: Rc<Class>,
+    fields: HashMap<String, Value>,
+}
+
+impl Instance {
+    pub fn new(class: Rc<Class>) -> Instance {
+        Instance {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Fields shadow methods; methods are bound to the instance they are read from.
+    pub fn get(instance: &Rc<RefCell<Instance>>, name: &Item) -> Result<Value, LoxError> {
+        if let Some(value) = instance.borrow().fields.get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+
+        let method = instance.borrow().class.find_method(&name.lexeme);
+        match method {
+            Some(method) => Ok(Value::Callable(Rc::new(method.bind(Rc::clone(instance))))),
+            None => Err(LoxError::new(
+                ErrorCode::UndefinedProperty,
+                format!("Undefined property '{}'.", name.lexeme),
+                name.span,
+            )),
+        }
+    }
+
+    pub fn set(&mut self, name: &Item, value: Value) {
+        self.fields.insert(name.lexeme.clone(), value);
+    }
+}
+
+impl fmt::Display for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
@@ -0,0 +1,106 @@
+use crate::errors::{ErrorCode, LoxError};
+use crate::interpreter::Value;
+use crate::token::Item;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Default)]
+pub struct Environment {
+    values: HashMap<String, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        Environment::default()
+    }
+
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Environment {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    /// The variables defined directly in this environment, sorted by name.
+    pub fn bindings(&self) -> Vec<(String, Value)> {
+        let mut bindings = self
+            .values
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect::<Vec<_>>();
+        bindings.sort_by(|(left, _), (right, _)| left.cmp(right));
+        bindings
+    }
+
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &Item) -> Result<Value, LoxError> {
+        if let Some(value) = self.values.get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get(name),
+            None => Err(undefined_variable(name)),
+        }
+    }
+
+    /// Looks `name` up in this environment only, without walking the enclosing chain.
+    pub fn get_local(&self, name: &str) -> Option<Value> {
+        self.values.get(name).cloned()
+    }
+
+    /// Reads `name` from the environment exactly `distance` hops up the chain, as computed by the
+    /// resolver.
+    pub fn get_at(&self, distance: usize, name: &Item) -> Result<Value, LoxError> {
+        if distance == 0 {
+            return self
+                .values
+                .get(&name.lexeme)
+                .cloned()
+                .ok_or_else(|| undefined_variable(name));
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get_at(distance - 1, name),
+            None => Err(undefined_variable(name)),
+        }
+    }
+
+    pub fn assign_at(
+        &mut self,
+        distance: usize,
+        name: &Item,
+        value: Value,
+    ) -> Result<(), LoxError> {
+        if distance == 0 {
+            self.values.insert(name.lexeme.clone(), value);
+            return Ok(());
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign_at(distance - 1, name, value),
+            None => Err(undefined_variable(name)),
+        }
+    }
+
+    pub fn assign(&mut self, name: &Item, value: Value) -> Result<(), LoxError> {
+        if let Some(slot) = self.values.get_mut(&name.lexeme) {
+            *slot = value;
+            return Ok(());
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(undefined_variable(name)),
+        }
+    }
+}
+
+fn undefined_variable(name: &Item) -> LoxError {
+    LoxError::new(
+        ErrorCode::UndefinedVariable,
+        format!("Undefined variable '{}'.", name.lexeme),
+        name.span,
+    )
+}
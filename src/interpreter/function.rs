@@ -0,0 +1,80 @@
+use crate::errors::LoxError;
+use crate::interpreter::class::Instance;
+use crate::interpreter::environment::Environment;
+use crate::interpreter::{Callable, Interpreter, Unwind, Value};
+use crate::stmt::FunctionDecl;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+/// A user-defined function together with the environment it was declared in.
+pub struct Function {
+    declaration: Rc<FunctionDecl>,
+    closure: Rc<RefCell<Environment>>,
+    is_initializer: bool,
+}
+
+impl Function {
+    pub fn new(
+        declaration: Rc<FunctionDecl>,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> Function {
+        Function {
+            declaration,
+            closure,
+            is_initializer,
+        }
+    }
+
+    /// Returns a copy of this method whose closure defines `this` as the given instance.
+    pub fn bind(&self, instance: Rc<RefCell<Instance>>) -> Function {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        environment.define("this", Value::Instance(instance));
+        Function::new(
+            Rc::clone(&self.declaration),
+            Rc::new(RefCell::new(environment)),
+            self.is_initializer,
+        )
+    }
+
+    fn this(&self) -> Value {
+        self.closure
+            .borrow()
+            .get_local("this")
+            .unwrap_or(Value::Nil)
+    }
+}
+
+impl Callable for Function {
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, LoxError> {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(&param.lexeme, argument);
+        }
+
+        let result =
+            interpreter.execute_block(&self.declaration.body, Rc::new(RefCell::new(environment)));
+        match result {
+            // An initializer always hands back the instance, even on an early bare `return;`.
+            Ok(()) | Err(Unwind::Return(_)) if self.is_initializer => Ok(self.this()),
+            Ok(()) => Ok(Value::Nil),
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(error)) => Err(error),
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.lexeme)
+    }
+}
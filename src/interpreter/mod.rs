@@ -0,0 +1,798 @@
+mod class;
+mod environment;
+mod function;
+mod native;
+mod value;
+
+pub use value::{Callable, Value};
+
+use crate::errors::{ErrorCode, LoxError};
+use crate::expr::{self, Expr, ExprId};
+use crate::stmt::{self, FunctionDecl, Stmt};
+use crate::token::{Item, Token};
+use class::{Class, Instance};
+use environment::Environment;
+use function::Function;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+// Calls nested deeper than this are taken to be runaway recursion, like the virtual machine's
+// `MAX_FRAMES`.
+const MAX_CALL_DEPTH: usize = 1024;
+
+// Native stack the calls may use unless told otherwise: half of the smallest stack Rust gives a
+// thread by default. Each call takes several native frames, a lot more in debug builds, so this
+// usually runs out before `MAX_CALL_DEPTH` is reached.
+const DEFAULT_STACK_LIMIT: usize = 1024 * 1024;
+
+/// Non-local exits out of statement execution: either a runtime error or a `return` carrying its
+/// value up to the enclosing call.
+pub enum Unwind {
+    Error(LoxError),
+    Return(Value),
+}
+
+impl From<LoxError> for Unwind {
+    fn from(error: LoxError) -> Unwind {
+        Unwind::Error(error)
+    }
+}
+
+pub struct Interpreter {
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    locals: HashMap<ExprId, usize>,
+    output: Rc<RefCell<dyn Write>>,
+    // Calls to functions and classes currently running.
+    depth: usize,
+    // Address of a local of the outermost call, from which the native stack used is measured.
+    stack_base: usize,
+    stack_limit: usize,
+}
+
+impl Interpreter {
+    /// Sends what `print` writes to `output` instead of stdout.
+    pub fn with_output(mut self, output: Rc<RefCell<dyn Write>>) -> Interpreter {
+        self.output = output;
+        self
+    }
+
+    /// Lets nested calls use up to `bytes` of native stack, 1 MiB by default, before they are
+    /// reported as a stack overflow. The thread running the interpreter needs a stack larger than
+    /// that.
+    pub fn with_stack_limit(mut self, bytes: usize) -> Interpreter {
+        self.stack_limit = bytes;
+        self
+    }
+
+    /// Records the scope distances computed by the resolver; references missing from the table
+    /// are globals.
+    pub fn resolve(&mut self, locals: HashMap<ExprId, usize>) {
+        self.locals.extend(locals);
+    }
+
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        self.globals.borrow().bindings()
+    }
+
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<(), LoxError> {
+        for statement in statements {
+            match self.execute(statement) {
+                Ok(()) => {}
+                Err(Unwind::Error(error)) => return Err(error),
+                Err(Unwind::Return(_)) => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
+        stmt::Visitor::visit_stmt(self, stmt)
+    }
+
+    pub(crate) fn execute_block(
+        &mut self,
+        statements: &[Stmt],
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<(), Unwind> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let result = statements
+            .iter()
+            .try_for_each(|statement| self.execute(statement));
+        self.environment = previous;
+        result
+    }
+
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Value, LoxError> {
+        expr::Visitor::visit_expr(self, expr)
+    }
+
+    fn look_up_variable(&self, id: ExprId, name: &Item) -> Result<Value, LoxError> {
+        match self.locals.get(&id) {
+            Some(distance) => self.environment.borrow().get_at(*distance, name),
+            None => self.globals.borrow().get(name),
+        }
+    }
+
+    fn error(&self, item: &Item, code: ErrorCode, message: &str) -> LoxError {
+        LoxError::new(code, message, item.span)
+    }
+
+    fn check_arity(&self, paren: &Item, arity: usize, count: usize) -> Result<(), LoxError> {
+        if arity != count {
+            return Err(self.error(
+                paren,
+                ErrorCode::ArityMismatch,
+                &format!("Expected {} arguments but got {}.", arity, count),
+            ));
+        }
+        Ok(())
+    }
+
+    fn number_operand(&self, operator: &Item, operand: &Value) -> Result<f64, LoxError> {
+        match operand {
+            Value::Number(value) => Ok(*value),
+            _ => Err(self.error(
+                operator,
+                ErrorCode::InvalidOperand,
+                "Operand must be a number.",
+            )),
+        }
+    }
+
+    fn number_operands(
+        &self,
+        operator: &Item,
+        left: &Value,
+        right: &Value,
+    ) -> Result<(f64, f64), LoxError> {
+        match (left, right) {
+            (Value::Number(left), Value::Number(right)) => Ok((*left, *right)),
+            _ => Err(self.error(
+                operator,
+                ErrorCode::InvalidOperand,
+                "Operands must be numbers.",
+            )),
+        }
+    }
+}
+
+impl expr::Visitor<Result<Value, LoxError>> for Interpreter {
+    fn new() -> Self {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals
+            .borrow_mut()
+            .define("clock", Value::Callable(Rc::new(native::Clock)));
+
+        Interpreter {
+            environment: Rc::clone(&globals),
+            globals,
+            locals: HashMap::new(),
+            output: Rc::new(RefCell::new(io::stdout())),
+            depth: 0,
+            stack_base: 0,
+            stack_limit: DEFAULT_STACK_LIMIT,
+        }
+    }
+
+    fn visit_expr_unary(&mut self, operator: &Item, expr: &Expr) -> Result<Value, LoxError> {
+        let right = self.evaluate(expr)?;
+        match operator.token {
+            Token::Minus => Ok(Value::Number(-self.number_operand(operator, &right)?)),
+            Token::Bang => Ok(Value::Bool(!right.is_truthy())),
+            _ => Err(self.error(operator, ErrorCode::Internal, "Unknown unary operator.")),
+        }
+    }
+
+    fn visit_expr_binary(
+        &mut self,
+        left: &Expr,
+        operator: &Item,
+        right: &Expr,
+    ) -> Result<Value, LoxError> {
+        let left = self.evaluate(left)?;
+        let right = self.evaluate(right)?;
+
+        match operator.token {
+            Token::Plus => match (left, right) {
+                (Value::Number(left), Value::Number(right)) => Ok(Value::Number(left + right)),
+                (Value::String(left), Value::String(right)) => Ok(Value::String(left + &right)),
+                _ => Err(self.error(
+                    operator,
+                    ErrorCode::InvalidOperand,
+                    "Operands must be two numbers or two strings.",
+                )),
+            },
+            Token::Minus => {
+                let (left, right) = self.number_operands(operator, &left, &right)?;
+                Ok(Value::Number(left - right))
+            }
+            Token::Star => {
+                let (left, right) = self.number_operands(operator, &left, &right)?;
+                Ok(Value::Number(left * right))
+            }
+            Token::Slash => {
+                let (left, right) = self.number_operands(operator, &left, &right)?;
+                Ok(Value::Number(left / right))
+            }
+            Token::Greater => {
+                let (left, right) = self.number_operands(operator, &left, &right)?;
+                Ok(Value::Bool(left > right))
+            }
+            Token::GreaterEqual => {
+                let (left, right) = self.number_operands(operator, &left, &right)?;
+                Ok(Value::Bool(left >= right))
+            }
+            Token::Lesser => {
+                let (left, right) = self.number_operands(operator, &left, &right)?;
+                Ok(Value::Bool(left < right))
+            }
+            Token::LesserEqual => {
+                let (left, right) = self.number_operands(operator, &left, &right)?;
+                Ok(Value::Bool(left <= right))
+            }
+            Token::EqualEqual => Ok(Value::Bool(left == right)),
+            Token::BangEqual => Ok(Value::Bool(left != right)),
+            _ => Err(self.error(operator, ErrorCode::Internal, "Unknown binary operator.")),
+        }
+    }
+
+    fn visit_expr_grouping(&mut self, expr: &Expr) -> Result<Value, LoxError> {
+        self.evaluate(expr)
+    }
+
+    fn visit_expr_literal(&mut self, value: &Item) -> Result<Value, LoxError> {
+        match &value.token {
+            Token::Nil => Ok(Value::Nil),
+            Token::True => Ok(Value::Bool(true)),
+            Token::False => Ok(Value::Bool(false)),
+            Token::Number(number) => Ok(Value::Number(*number)),
+            Token::String(string) => Ok(Value::String(string.clone())),
+            _ => Err(self.error(value, ErrorCode::Internal, "Unknown literal.")),
+        }
+    }
+
+    fn visit_expr_logical(
+        &mut self,
+        left: &Expr,
+        operator: &Item,
+        right: &Expr,
+    ) -> Result<Value, LoxError> {
+        let left = self.evaluate(left)?;
+        let short_circuits = match operator.token {
+            Token::Or => left.is_truthy(),
+            _ => !left.is_truthy(),
+        };
+        if short_circuits {
+            return Ok(left);
+        }
+        self.evaluate(right)
+    }
+
+    fn visit_expr_variable(&mut self, id: ExprId, name: &Item) -> Result<Value, LoxError> {
+        self.look_up_variable(id, name)
+    }
+
+    fn visit_expr_assign(
+        &mut self,
+        id: ExprId,
+        name: &Item,
+        value: &Expr,
+    ) -> Result<Value, LoxError> {
+        let value = self.evaluate(value)?;
+        match self.locals.get(&id) {
+            Some(distance) => {
+                self.environment
+                    .borrow_mut()
+                    .assign_at(*distance, name, value.clone())?
+            }
+            None => self.globals.borrow_mut().assign(name, value.clone())?,
+        }
+        Ok(value)
+    }
+
+    fn visit_expr_call(
+        &mut self,
+        callee: &Expr,
+        paren: &Item,
+        arguments: &[Expr],
+    ) -> Result<Value, LoxError> {
+        let callee = self.evaluate(callee)?;
+        let arguments = arguments
+            .iter()
+            .map(|argument| self.evaluate(argument))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let marker = 0u8;
+        let here = std::hint::black_box(&marker) as *const u8 as usize;
+        if self.depth == 0 {
+            self.stack_base = here;
+        }
+        if self.depth == MAX_CALL_DEPTH || self.stack_base.abs_diff(here) > self.stack_limit {
+            return Err(self.error(paren, ErrorCode::StackOverflow, "Stack overflow."));
+        }
+        self.depth += 1;
+        let result = match callee {
+            Value::Callable(callable) => self
+                .check_arity(paren, callable.arity(), arguments.len())
+                .and_then(|()| callable.call(self, arguments)),
+            Value::Class(class) => self
+                .check_arity(paren, class.arity(), arguments.len())
+                .and_then(|()| Class::instantiate(&class, self, arguments)),
+            _ => Err(self.error(
+                paren,
+                ErrorCode::NotCallable,
+                "Can only call functions and classes.",
+            )),
+        };
+        self.depth -= 1;
+        result
+    }
+
+    fn visit_expr_get(&mut self, object: &Expr, name: &Item) -> Result<Value, LoxError> {
+        match self.evaluate(object)? {
+            Value::Instance(instance) => Instance::get(&instance, name),
+            _ => Err(self.error(
+                name,
+                ErrorCode::NotAnInstance,
+                "Only instances have properties.",
+            )),
+        }
+    }
+
+    fn visit_expr_set(
+        &mut self,
+        object: &Expr,
+        name: &Item,
+        value: &Expr,
+    ) -> Result<Value, LoxError> {
+        let instance = match self.evaluate(object)? {
+            Value::Instance(instance) => instance,
+            _ => {
+                return Err(self.error(
+                    name,
+                    ErrorCode::NotAnInstance,
+                    "Only instances have fields.",
+                ))
+            }
+        };
+        let value = self.evaluate(value)?;
+        instance.borrow_mut().set(name, value.clone());
+        Ok(value)
+    }
+
+    fn visit_expr_this(&mut self, id: ExprId, keyword: &Item) -> Result<Value, LoxError> {
+        self.look_up_variable(id, keyword)
+    }
+
+    // `super` lives in the environment wrapping the class's methods, and `this` in the one bound
+    // right inside it, one hop closer to the method body.
+    fn visit_expr_super(
+        &mut self,
+        id: ExprId,
+        keyword: &Item,
+        method: &Item,
+    ) -> Result<Value, LoxError> {
+        let distance = match self.locals.get(&id) {
+            Some(distance) => *distance,
+            None => {
+                return Err(self.error(
+                    keyword,
+                    ErrorCode::Internal,
+                    "Can't use 'super' outside of a class.",
+                ))
+            }
+        };
+        let superclass = match self.environment.borrow().get_at(distance, keyword)? {
+            Value::Class(superclass) => superclass,
+            _ => {
+                return Err(self.error(
+                    keyword,
+                    ErrorCode::SuperclassNotClass,
+                    "Superclass must be a class.",
+                ))
+            }
+        };
+        let this = Item::new("this".to_string(), Token::This, keyword.span);
+        let instance = match self.environment.borrow().get_at(distance - 1, &this)? {
+            Value::Instance(instance) => instance,
+            _ => {
+                return Err(self.error(
+                    keyword,
+                    ErrorCode::Internal,
+                    "Can't use 'super' outside of a method.",
+                ))
+            }
+        };
+
+        match superclass.find_method(&method.lexeme) {
+            Some(found) => Ok(Value::Callable(Rc::new(found.bind(instance)))),
+            None => Err(self.error(
+                method,
+                ErrorCode::UndefinedProperty,
+                &format!("Undefined property '{}'.", method.lexeme),
+            )),
+        }
+    }
+}
+
+impl stmt::Visitor<Result<(), Unwind>> for Interpreter {
+    fn visit_stmt_expression(&mut self, expr: &Expr) -> Result<(), Unwind> {
+        self.evaluate(expr)?;
+        Ok(())
+    }
+
+    fn visit_stmt_print(&mut self, expr: &Expr) -> Result<(), Unwind> {
+        let value = self.evaluate(expr)?;
+        writeln!(self.output.borrow_mut(), "{}", value).map_err(|error| {
+            LoxError::new(
+                ErrorCode::Internal,
+                format!("Could not write output: {}.", error),
+                expr.span,
+            )
+        })?;
+        Ok(())
+    }
+
+    fn visit_stmt_var(&mut self, name: &Item, initializer: Option<&Expr>) -> Result<(), Unwind> {
+        let value = match initializer {
+            Some(initializer) => self.evaluate(initializer)?,
+            None => Value::Nil,
+        };
+        self.environment.borrow_mut().define(&name.lexeme, value);
+        Ok(())
+    }
+
+    fn visit_stmt_block(&mut self, statements: &[Stmt]) -> Result<(), Unwind> {
+        let environment = Environment::with_enclosing(Rc::clone(&self.environment));
+        self.execute_block(statements, Rc::new(RefCell::new(environment)))
+    }
+
+    fn visit_stmt_if(
+        &mut self,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: Option<&Stmt>,
+    ) -> Result<(), Unwind> {
+        if self.evaluate(condition)?.is_truthy() {
+            self.execute(then_branch)?;
+        } else if let Some(else_branch) = else_branch {
+            self.execute(else_branch)?;
+        }
+        Ok(())
+    }
+
+    fn visit_stmt_function(&mut self, declaration: &Rc<FunctionDecl>) -> Result<(), Unwind> {
+        let function = Function::new(Rc::clone(declaration), Rc::clone(&self.environment), false);
+        self.environment
+            .borrow_mut()
+            .define(&declaration.name.lexeme, Value::Callable(Rc::new(function)));
+        Ok(())
+    }
+
+    fn visit_stmt_return(&mut self, _keyword: &Item, value: Option<&Expr>) -> Result<(), Unwind> {
+        let value = match value {
+            Some(value) => self.evaluate(value)?,
+            None => Value::Nil,
+        };
+        Err(Unwind::Return(value))
+    }
+
+    fn visit_stmt_class(
+        &mut self,
+        name: &Item,
+        superclass: Option<&Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) -> Result<(), Unwind> {
+        let superclass = match superclass {
+            Some(superclass_expr) => match self.evaluate(superclass_expr)? {
+                Value::Class(superclass) => Some(superclass),
+                _ => {
+                    return Err(Unwind::Error(
+                        LoxError::new(
+                            ErrorCode::SuperclassNotClass,
+                            "Superclass must be a class.",
+                            superclass_expr.span,
+                        )
+                        .with_label(name.span, "in this class declaration"),
+                    ));
+                }
+            },
+            None => None,
+        };
+
+        self.environment
+            .borrow_mut()
+            .define(&name.lexeme, Value::Nil);
+
+        let enclosing = Rc::clone(&self.environment);
+        if let Some(superclass) = &superclass {
+            let mut environment = Environment::with_enclosing(Rc::clone(&enclosing));
+            environment.define("super", Value::Class(Rc::clone(superclass)));
+            self.environment = Rc::new(RefCell::new(environment));
+        }
+
+        let mut class_methods = HashMap::new();
+        for method in methods {
+            let function = Function::new(
+                Rc::clone(method),
+                Rc::clone(&self.environment),
+                method.name.lexeme == "init",
+            );
+            class_methods.insert(method.name.lexeme.clone(), Rc::new(function));
+        }
+        let class = Class::new(name.lexeme.clone(), superclass, class_methods);
+
+        self.environment = enclosing;
+        self.environment
+            .borrow_mut()
+            .assign(name, Value::Class(Rc::new(class)))?;
+        Ok(())
+    }
+
+    fn visit_stmt_while(&mut self, condition: &Expr, body: &Stmt) -> Result<(), Unwind> {
+        while self.evaluate(condition)?.is_truthy() {
+            self.execute(body)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::{ErrorCode, LoxError};
+    use crate::expr::Visitor;
+    use crate::interpreter::{Interpreter, Value};
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+    use crate::stmt::Stmt;
+
+    // Runs every statement but the last one, which must be an expression statement whose value
+    // is returned.
+    fn evaluate(source: &str) -> Result<Value, LoxError> {
+        let mut statements =
+            Parser::new(Scanner::new(source).scan_tokens().expect("Failed scanning"))
+                .parse()
+                .expect("Failed parsing");
+        let locals = Resolver::new()
+            .resolve(&statements)
+            .expect("Failed resolving");
+        let last = statements.pop().expect("Empty program");
+        let mut interpreter = Interpreter::new();
+        interpreter.resolve(locals);
+        interpreter.interpret(&statements)?;
+        match &last {
+            Stmt::Expression(expr) => interpreter.visit_expr(expr),
+            _ => panic!("Expected an expression statement"),
+        }
+    }
+
+    #[test]
+    fn evaluate_expressions() {
+        assert_eq!(Value::Number(7.0), evaluate("1 + 2 * 3;").unwrap());
+        assert_eq!(Value::Number(-2.5), evaluate("-(10 / 4);").unwrap());
+        assert_eq!(
+            Value::String("foobar".to_string()),
+            evaluate("\"foo\" + \"bar\";").unwrap()
+        );
+        assert_eq!(Value::Bool(true), evaluate("1 < 2 == !false;").unwrap());
+        assert_eq!(Value::Bool(false), evaluate("nil == false;").unwrap());
+        assert_eq!(Value::Bool(true), evaluate("\"a\" != 1;").unwrap());
+        assert_eq!(
+            Value::String("yes".to_string()),
+            evaluate("nil or \"yes\";").unwrap()
+        );
+        assert_eq!(Value::Nil, evaluate("nil and 1;").unwrap());
+    }
+
+    #[test]
+    fn evaluate_type_errors() {
+        let error = evaluate("1 + \"a\";").unwrap_err();
+        assert_eq!(
+            "Operands must be two numbers or two strings.",
+            error.message
+        );
+
+        let error = evaluate("-\"a\";").unwrap_err();
+        assert_eq!("Operand must be a number.", error.message);
+
+        let error = evaluate("true < 1;").unwrap_err();
+        assert_eq!("Operands must be numbers.", error.message);
+    }
+
+    #[test]
+    fn stringify_values() {
+        assert_eq!("3", evaluate("1 + 2;").unwrap().to_string());
+        assert_eq!("2.5", evaluate("5 / 2;").unwrap().to_string());
+        assert_eq!("nil", evaluate("nil;").unwrap().to_string());
+        assert_eq!("false", evaluate("!true;").unwrap().to_string());
+    }
+
+    #[test]
+    fn variables_and_scopes() {
+        assert_eq!(Value::Nil, evaluate("var a; a;").unwrap());
+        assert_eq!(
+            Value::Number(2.0),
+            evaluate("var a = 1; a = a + 1; a;").unwrap()
+        );
+        assert_eq!(
+            Value::Number(2.0),
+            evaluate("var a = 1; var a = 2; a;").unwrap()
+        );
+        assert_eq!(
+            Value::Number(1.0),
+            evaluate("var a = 1; { var a = 2; { var a = 3; } } a;").unwrap()
+        );
+        assert_eq!(
+            Value::Number(3.0),
+            evaluate("var a = 1; { var b = 2; { a = a + b; } } a;").unwrap()
+        );
+        assert_eq!(
+            Value::Number(6.0),
+            evaluate("var sum = 0; for (var i = 0; i < 4; i = i + 1) sum = sum + i; sum;").unwrap()
+        );
+    }
+
+    #[test]
+    fn undefined_variables() {
+        let error = evaluate("a;").unwrap_err();
+        assert_eq!("Undefined variable 'a'.", error.message);
+
+        let error = evaluate("{ var b = 1; } b;").unwrap_err();
+        assert_eq!("Undefined variable 'b'.", error.message);
+
+        let error = evaluate("c = 1;").unwrap_err();
+        assert_eq!("Undefined variable 'c'.", error.message);
+    }
+
+    #[test]
+    fn functions_and_closures() {
+        assert_eq!(
+            Value::Number(3.0),
+            evaluate("fun add(a, b) { return a + b; } add(1, 2);").unwrap()
+        );
+        assert_eq!(Value::Nil, evaluate("fun noop() {} noop();").unwrap());
+        assert_eq!(
+            Value::Number(55.0),
+            evaluate(
+                "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); } fib(10);"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            Value::Number(1.0),
+            evaluate("fun f() { while (true) { { return 1; } } } f();").unwrap()
+        );
+        assert_eq!(
+            Value::Number(2.0),
+            evaluate(
+                "fun makeCounter() { var i = 0; fun count() { i = i + 1; return i; } return count; } \
+                 var counter = makeCounter(); counter(); counter();"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            Value::String("globalglobal".to_string()),
+            evaluate(
+                "var a = \"global\"; var r; \
+                 { fun show() { return a; } var first = show(); var a = \"block\"; r = first + show(); } \
+                 r;"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            "<fn add>",
+            evaluate("fun add() {} add;").unwrap().to_string()
+        );
+        assert_eq!("<native fn>", evaluate("clock;").unwrap().to_string());
+    }
+
+    #[test]
+    fn call_errors() {
+        let error = evaluate("fun f(a) {}\n\nf(1, 2);").unwrap_err();
+        assert_eq!("Expected 1 arguments but got 2.", error.message);
+        assert_eq!(3, error.line());
+        assert_eq!(ErrorCode::ArityMismatch, error.code);
+
+        let error = evaluate("\"f\"();").unwrap_err();
+        assert_eq!("Can only call functions and classes.", error.message);
+
+        // On the small stack of a test thread.
+        let error = evaluate("fun f() { f(); }\nf();").unwrap_err();
+        assert_eq!("Stack overflow.", error.message);
+        assert_eq!(1, error.line());
+        assert_eq!(ErrorCode::StackOverflow, error.code);
+        let error = evaluate(
+            "class A { m(n) { if (true) { while (true) { return 1 + this.m(n + 1); } } } } A().m(0);",
+        )
+        .unwrap_err();
+        assert_eq!(ErrorCode::StackOverflow, error.code);
+    }
+
+    #[test]
+    fn classes_and_instances() {
+        assert_eq!("Foo", evaluate("class Foo {} Foo;").unwrap().to_string());
+        assert_eq!(
+            "Foo instance",
+            evaluate("class Foo {} Foo();").unwrap().to_string()
+        );
+        assert_eq!(
+            Value::Number(3.0),
+            evaluate("class P {} var p = P(); p.x = 1; p.y = 2; p.x + p.y;").unwrap()
+        );
+        assert_eq!(
+            Value::String("hi bob".to_string()),
+            evaluate(
+                "class Greeter { init(name) { this.name = name; } greet() { return \"hi \" + this.name; } } \
+                 Greeter(\"bob\").greet();"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            Value::Number(1.0),
+            evaluate(
+                "class C { init() { this.n = 1; return; } } var c = C(); c.n = 2; c.init().n;"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            Value::String("bound".to_string()),
+            evaluate(
+                "class C { m() { return this.v; } } var c = C(); c.v = \"bound\"; var m = c.m; m();"
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn inheritance_and_super() {
+        assert_eq!(
+            Value::String("A.m".to_string()),
+            evaluate("class A { m() { return \"A.m\"; } } class B < A {} B().m();").unwrap()
+        );
+        assert_eq!(
+            Value::String("B.m A.m".to_string()),
+            evaluate(
+                "class A { m() { return \"A.m\"; } } \
+                 class B < A { m() { return \"B.m \" + super.m(); } } \
+                 B().m();"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            Value::String("A.m".to_string()),
+            evaluate(
+                "class A { m() { return \"A.m\"; } } \
+                 class B < A { test() { return super.m(); } } \
+                 class C < B { m() { return \"C.m\"; } } \
+                 C().test();"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            Value::Number(2.0),
+            evaluate("class A { init(a, b) { this.b = b; } } class B < A {} B(1, 2).b;").unwrap()
+        );
+    }
+
+    #[test]
+    fn property_errors() {
+        let error = evaluate("class Foo {} Foo().bar;").unwrap_err();
+        assert_eq!("Undefined property 'bar'.", error.message);
+
+        let error = evaluate("var a = 1; a.b;").unwrap_err();
+        assert_eq!("Only instances have properties.", error.message);
+
+        let error = evaluate("var a = 1; a.b = 2;").unwrap_err();
+        assert_eq!("Only instances have fields.", error.message);
+
+        let error = evaluate("var A = 1; class B < A {} B;").unwrap_err();
+        assert_eq!("Superclass must be a class.", error.message);
+
+        let error = evaluate("class A { init(a) {} } A();").unwrap_err();
+        assert_eq!("Expected 1 arguments but got 0.", error.message);
+    }
+}
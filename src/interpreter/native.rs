@@ -0,0 +1,31 @@
+use crate::errors::LoxError;
+use crate::interpreter::{Callable, Interpreter, Value};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `clock()`: seconds elapsed since the Unix epoch, as a float.
+pub struct Clock;
+
+impl Callable for Clock {
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        _arguments: Vec<Value>,
+    ) -> Result<Value, LoxError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs_f64())
+            .unwrap_or(0.0);
+        Ok(Value::Number(now))
+    }
+}
+
+impl fmt::Display for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn>")
+    }
+}
@@ -0,0 +1,78 @@
+use crate::errors::LoxError;
+use crate::interpreter::class::{Class, Instance};
+use crate::interpreter::Interpreter;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+pub trait Callable: fmt::Display {
+    fn arity(&self) -> usize;
+
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>)
+        -> Result<Value, LoxError>;
+}
+
+#[derive(Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Callable(Rc<dyn Callable>),
+    Class(Rc<Class>),
+    Instance(Rc<RefCell<Instance>>),
+    /// An object of the virtual machine other than a string, e.g. a function or an instance. It
+    /// stays in the virtual machine's heap, which only hands out how `print` shows it, so it is
+    /// never equal to another value.
+    Object(String),
+}
+
+impl Value {
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Nil => false,
+            Value::Bool(value) => *value,
+            _ => true,
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        use Value::*;
+        match (self, other) {
+            (Nil, Nil) => true,
+            (Bool(left), Bool(right)) => left == right,
+            (Number(left), Number(right)) => left == right,
+            (String(left), String(right)) => left == right,
+            (Callable(left), Callable(right)) => Rc::ptr_eq(left, right),
+            (Class(left), Class(right)) => Rc::ptr_eq(left, right),
+            (Instance(left), Instance(right)) => Rc::ptr_eq(left, right),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Number(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{}", value),
+            Value::Callable(callable) => write!(f, "{}", callable),
+            Value::Class(class) => write!(f, "{}", class),
+            Value::Instance(instance) => write!(f, "{}", instance.borrow()),
+            Value::Object(object) => write!(f, "{}", object),
+        }
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::String(value) => write!(f, "{:?}", value),
+            _ => write!(f, "{}", self),
+        }
+    }
+}
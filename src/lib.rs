@@ -1,22 +1,425 @@
+// Diagnostics carry spans, labels and help, and are only built on the failure path.
+#![allow(clippy::result_large_err)]
+
+use std::cell::RefCell;
+use std::env;
 use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::rc::Rc;
+use std::str::FromStr;
 
-mod scanner;
-mod token;
+mod conformance;
+mod diagnostic;
 mod errors;
 mod expr;
+mod formatter;
+mod interpreter;
+mod parser;
+mod resolver;
+mod scanner;
+mod span;
+mod stmt;
+mod token;
+mod vm;
+
+pub use conformance::{run_tests, TestReport};
+pub use diagnostic::{ErrorFormat, Renderer};
+pub use errors::{ErrorCode, Label, LoxError, Phase, Severity};
+pub use formatter::format_source;
+pub use interpreter::Value;
+pub use span::{Position, SourceMap, Span};
+pub use token::{Item, Token, TokenStream};
 
+use expr::{Visitor, VisitorPrinter};
+use interpreter::Interpreter;
+use parser::Parser;
+use resolver::Resolver;
 use scanner::Scanner;
+use stmt::Stmt;
+use vm::Vm;
+
+/// Which engine runs programs: the tree-walking interpreter or the bytecode virtual machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Tree,
+    Vm,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(backend: &str) -> Result<Backend, String> {
+        match backend {
+            "tree" => Ok(Backend::Tree),
+            "vm" => Ok(Backend::Vm),
+            _ => Err(format!(
+                "Unknown backend '{}', expected 'tree' or 'vm'.",
+                backend
+            )),
+        }
+    }
+}
+
+/// An interpreter session whose global environment outlives a single `run`, so that consecutive
+/// sources (e.g. REPL lines) see each other's declarations.
+pub struct Lox {
+    interpreter: Interpreter,
+    vm: Vm,
+    backend: Backend,
+    output: Rc<RefCell<dyn Write>>,
+}
+
+impl Lox {
+    pub fn new() -> Lox {
+        Lox {
+            interpreter: Interpreter::new(),
+            vm: Vm::new(),
+            backend: Backend::Tree,
+            output: Rc::new(RefCell::new(io::stdout())),
+        }
+    }
+
+    /// Sends what the program prints to `output` instead of stdout. Keep a clone of the `Rc` to
+    /// read it back, e.g. from a `Vec<u8>`.
+    pub fn with_output(mut self, output: Rc<RefCell<dyn Write>>) -> Lox {
+        self.interpreter = self.interpreter.with_output(Rc::clone(&output));
+        self.vm = self.vm.with_output(Rc::clone(&output));
+        self.output = output;
+        self
+    }
+
+    /// Runs programs on `backend`. The virtual machine hands objects other than strings back as
+    /// `Value::Object`.
+    pub fn with_backend(mut self, backend: Backend) -> Lox {
+        self.backend = backend;
+        self
+    }
+
+    /// Lets the tree-walking interpreter use up to `bytes` of native stack for nested calls, 1 MiB
+    /// by default, before reporting a stack overflow. Only raise it on a thread whose stack is
+    /// larger than that.
+    pub fn with_stack_limit(mut self, bytes: usize) -> Lox {
+        self.interpreter = self.interpreter.with_stack_limit(bytes);
+        self
+    }
+
+    /// Has the virtual machine print its stack and each instruction to stderr as it runs them.
+    pub fn with_trace(mut self, trace: bool) -> Lox {
+        self.vm = self.vm.with_trace(trace);
+        self
+    }
+
+    /// Has the virtual machine collect garbage before every allocation, so that objects it forgot
+    /// to keep reachable are freed, and their use caught, as early as possible.
+    pub fn with_gc_stress(mut self, stress: bool) -> Lox {
+        self.vm = self.vm.with_gc_stress(stress);
+        self
+    }
+
+    /// Runs `source` in this session and returns the value of its final expression statement, or
+    /// nil. `name` is recorded in the error as the source that failed, e.g. its path. Only the
+    /// first error is returned; `check` lists every compile error.
+    pub fn run_source(&mut self, source: &str, name: &str) -> Result<Value, LoxError> {
+        match self.interpret(source, false) {
+            Ok(value) => Ok(value.unwrap_or(Value::Nil)),
+            Err(mut errors) => Err(errors.remove(0).with_file(name)),
+        }
+    }
+
+    /// Runs `source` in this session. Errors are returned rather than reported, so that the caller
+    /// can render them and tell compile errors from runtime ones by their phase.
+    pub fn run(&mut self, source: &str) -> Result<(), Vec<LoxError>> {
+        self.interpret(source, false).map(|_| ())
+    }
+
+    /// Every global variable of the backend with its current value, sorted by name.
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        match self.backend {
+            Backend::Tree => self.interpreter.globals(),
+            Backend::Vm => self.vm.globals(),
+        }
+    }
+
+    /// Runs a file in this session, so that its declarations stay visible afterwards.
+    pub fn run_file(&mut self, filepath: &str, format: ErrorFormat) -> io::Result<()> {
+        let code = fs::read_to_string(filepath)?;
+        if let Err(errors) = self.run(&code) {
+            report_errors(filepath, &code, &errors, format);
+        }
+        Ok(())
+    }
+
+    /// Runs input typed in a REPL: the final expression statement may leave out its `;`, in which
+    /// case its value is printed. With its `;`, as in `f();`, it only runs.
+    pub fn run_interactive(&mut self, source: &str) {
+        match self.interpret(source, true) {
+            Ok(Some(value)) => {
+                if let Err(error) = writeln!(self.output.borrow_mut(), "{}", value) {
+                    eprintln!("Could not write output: {}.", error);
+                }
+            }
+            Ok(None) => {}
+            Err(errors) => report(source, &errors),
+        }
+    }
+
+    // Stops at the first phase that reports errors, so a program that fails to compile never runs.
+    // Hands back the value of the final expression statement, which interactive sources may
+    // write without its `;`; they only hand it back when they do.
+    fn interpret(
+        &mut self,
+        source: &str,
+        interactive: bool,
+    ) -> Result<Option<Value>, Vec<LoxError>> {
+        let tokens = Scanner::new(source).scan_tokens()?;
+        // The item before the end of file.
+        let terminated = matches!(
+            tokens.iter().rev().nth(1),
+            Some(item) if item.token == Token::SemiColon
+        );
+        let mut parser = Parser::new(tokens);
+        if interactive {
+            parser = parser.with_trailing_expression();
+        }
+        let mut statements = parser.parse()?;
+        let locals = Resolver::new().resolve(&statements)?;
+        let has_value =
+            matches!(statements.last(), Some(Stmt::Expression(_))) && !(interactive && terminated);
+
+        let value = if self.backend == Backend::Vm {
+            self.vm.interpret(&statements)?
+        } else {
+            self.interpreter.resolve(locals);
+            let last = match statements.last() {
+                Some(Stmt::Expression(_)) => statements.pop(),
+                _ => None,
+            };
+            self.interpreter
+                .interpret(&statements)
+                .map_err(|error| vec![error])?;
+            match last {
+                Some(Stmt::Expression(expr)) => self
+                    .interpreter
+                    .evaluate(&expr)
+                    .map_err(|error| vec![error])?,
+                _ => Value::Nil,
+            }
+        };
+        Ok(if has_value { Some(value) } else { None })
+    }
+}
+
+impl Default for Lox {
+    fn default() -> Lox {
+        Lox::new()
+    }
+}
+
+/// Whether `source` ends inside a string or with an unclosed `(` or `{`, in which case a REPL
+/// should read more lines before running it.
+pub fn is_incomplete(source: &str) -> bool {
+    match Scanner::new(source).scan_tokens() {
+        Ok(items) => {
+            let depth = items.iter().fold(0isize, |depth, item| match item.token {
+                Token::LeftParen | Token::LeftBrace => depth + 1,
+                Token::RightParen | Token::RightBrace => depth - 1,
+                _ => depth,
+            });
+            depth > 0
+        }
+        Err(errors) => errors
+            .iter()
+            .any(|error| error.code == ErrorCode::UnterminatedString),
+    }
+}
 
 pub fn execute(source: &str) {
-    let scanner = Scanner::new(source);
-    let tokens = scanner.scan_tokens();
+    if let Err(errors) = Lox::new().run(source) {
+        report(source, &errors);
+    }
+}
 
-    for token in tokens {
-        println!("{:?}", token.lexeme);
+fn report(source: &str, errors: &[LoxError]) {
+    for error in errors {
+        eprintln!("{}", error.brief(source));
     }
 }
 
-pub fn execute_file(filepath: String) {
-    let code = fs::read_to_string(filepath).expect("Something went wrong reading the file");
-    execute(&code);
+/// Renders `errors` found in `source` to stderr, naming the source `name` (a path, or e.g.
+/// `<stdin>`).
+pub fn report_errors(name: &str, source: &str, errors: &[LoxError], format: ErrorFormat) {
+    let renderer = Renderer::new(name, source)
+        .with_colour(use_colour())
+        .with_format(format);
+    for error in errors {
+        eprintln!("{}", renderer.render(error));
+    }
+}
+
+/// Prints the syntax tree of every statement in `source`. An `interactive` source is parsed like
+/// REPL input, so it may end with an expression without its `;`.
+pub fn print_ast(source: &str, interactive: bool) -> Result<(), Vec<LoxError>> {
+    let tokens = Scanner::new(source).scan_tokens()?;
+    let mut parser = Parser::new(tokens);
+    if interactive {
+        parser = parser.with_trailing_expression();
+    }
+    let statements = parser.parse()?;
+    let mut printer = VisitorPrinter::new();
+    for statement in statements.iter() {
+        println!("{}", printer.print_stmt(statement));
+    }
+    Ok(())
+}
+
+pub fn print_tokens(source: &str) -> Result<(), Vec<LoxError>> {
+    for token in Scanner::new(source).scan_tokens()?.iter() {
+        println!("{}", token);
+    }
+    Ok(())
+}
+
+/// The bytecode the virtual machine runs for `source`, listed function by function.
+pub fn disassemble(source: &str) -> Result<String, Vec<LoxError>> {
+    let tokens = Scanner::new(source).scan_tokens()?;
+    let statements = Parser::new(tokens).parse()?;
+    Resolver::new().resolve(&statements)?;
+    vm::disassemble(&statements)
+}
+
+/// Reports the errors a program would fail to compile with, without running it.
+pub fn check(source: &str) -> Result<(), Vec<LoxError>> {
+    let tokens = Scanner::new(source).scan_tokens()?;
+    let statements = Parser::new(tokens).parse()?;
+    Resolver::new().resolve(&statements)?;
+    Ok(())
+}
+
+pub fn execute_file(filepath: String, format: ErrorFormat) -> io::Result<()> {
+    Lox::new().run_file(&filepath, format)
+}
+
+// Colour only goes to a terminal, and never when the user opted out through `NO_COLOR`.
+fn use_colour() -> bool {
+    env::var_os("NO_COLOR").is_none() && io::stderr().is_terminal()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        execute_file, is_incomplete, print_ast, Backend, ErrorCode, ErrorFormat, Lox, Value,
+    };
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn detect_incomplete_input() {
+        assert!(is_incomplete("fun f() {\n"));
+        assert!(is_incomplete("print (1 +\n"));
+        assert!(is_incomplete("var s = \"multi\nline"));
+        assert!(is_incomplete("{ if (a) { print a; }\n"));
+        assert!(!is_incomplete("fun f() {}\n"));
+        assert!(!is_incomplete("print 1 +\n"));
+        assert!(!is_incomplete("}\n"));
+        assert!(!is_incomplete("@ {"));
+    }
+
+    #[test]
+    fn interactive_state_and_values() {
+        let mut lox = Lox::new();
+        assert_eq!(Ok(None), lox.interpret("var a = 1;", true));
+        assert_eq!(Ok(Some(Value::Number(3.0))), lox.interpret("a + 2", true));
+        assert_eq!(Ok(Some(Value::Number(2.0))), lox.interpret("a = 2", true));
+        assert_eq!(Ok(None), lox.interpret("a = 2;", true));
+        assert_eq!(Ok(Some(Value::Number(4.0))), lox.interpret("a + 2;", false));
+        assert!(lox.interpret("a + 2", false).is_err());
+
+        lox.interpret("fun f() {}", true).unwrap();
+        let globals = lox
+            .globals()
+            .into_iter()
+            .map(|(name, value)| format!("{} = {}", name, value))
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["a = 2", "clock = <native fn>", "f = <fn f>"], globals);
+    }
+
+    #[test]
+    fn interactive_values_on_the_vm() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut lox = Lox::new()
+            .with_backend(Backend::Vm)
+            .with_output(output.clone());
+        lox.run_interactive("var a = 1;");
+        lox.run_interactive("a + 2");
+        lox.run_interactive("a = 2;");
+        lox.run_interactive("fun f() {} f");
+        assert_eq!(b"3\n<fn f>\n", &output.borrow()[..]);
+        let names = lox
+            .globals()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["a", "clock", "f"], names);
+    }
+
+    #[test]
+    fn print_ast_of_interactive_input() {
+        assert_eq!(Ok(()), print_ast("a = 1 + 2", true));
+        let errors = print_ast("a = 1 + 2", false).unwrap_err();
+        assert_eq!("Expect ';' after expression.", errors[0].message);
+    }
+
+    #[test]
+    fn run_source_into_a_sink() {
+        for backend in [Backend::Tree, Backend::Vm] {
+            let output = Rc::new(RefCell::new(Vec::new()));
+            let mut lox = Lox::new().with_backend(backend).with_output(output.clone());
+            assert_eq!(
+                Ok(Value::Nil),
+                lox.run_source("print 1; var a = 2;", "first.lox")
+            );
+            assert_eq!(
+                Ok(Value::Number(3.0)),
+                lox.run_source("print a; a + 1;", "second.lox")
+            );
+            assert_eq!(
+                Ok(Value::String("ab".to_string())),
+                lox.run_source("\"a\" + \"b\";", "strings.lox")
+            );
+            assert_eq!(b"1\n2\n", &output.borrow()[..]);
+
+            let error = lox.run_source("print b;", "third.lox").unwrap_err();
+            assert_eq!(ErrorCode::UndefinedVariable, error.code);
+            assert_eq!(Some("third.lox".to_string()), error.file);
+            let error = lox.run_source("print;\nprint;", "fourth.lox").unwrap_err();
+            assert_eq!(
+                (ErrorCode::ExpectedExpression, 1),
+                (error.code, error.line())
+            );
+        }
+    }
+
+    // On the stack of a test thread, which is smaller than a main thread's.
+    #[test]
+    fn deep_recursion_is_an_error() {
+        for backend in [Backend::Tree, Backend::Vm] {
+            let mut lox = Lox::new().with_backend(backend);
+            let error = lox
+                .run_source("fun f() { f(); } f();", "function.lox")
+                .unwrap_err();
+            assert_eq!(ErrorCode::StackOverflow, error.code);
+            let error = lox
+                .run_source(
+                    "class A { m(n) { return this.m(n + 1); } } A().m(0);",
+                    "method.lox",
+                )
+                .unwrap_err();
+            assert_eq!(ErrorCode::StackOverflow, error.code);
+        }
+    }
+
+    #[test]
+    fn missing_file_is_an_error() {
+        assert!(execute_file("missing.lox".to_string(), ErrorFormat::Human).is_err());
+    }
 }
@@ -0,0 +1,748 @@
+use crate::errors::{ErrorCode, LoxError};
+use crate::expr::{Expr, ExprId, ExprKind};
+use crate::stmt::{FunctionDecl, Stmt};
+use crate::token::{Item, Token, TokenStream};
+use std::rc::Rc;
+
+const MAX_ARGUMENTS: usize = 255;
+
+pub struct Parser {
+    tokens: TokenStream,
+    errors: Vec<LoxError>,
+    trailing_expression: bool,
+}
+
+impl Parser {
+    pub fn new(items: Vec<Item>) -> Parser {
+        Parser {
+            tokens: TokenStream::new(items),
+            errors: vec![],
+            trailing_expression: false,
+        }
+    }
+
+    /// Lets the last expression statement of the source leave out its `;`, as typed in a REPL.
+    pub fn with_trailing_expression(mut self) -> Parser {
+        self.trailing_expression = true;
+        self
+    }
+
+    pub fn parse(self) -> Result<Vec<Stmt>, Vec<LoxError>> {
+        let (statements, errors) = self.parse_recovering();
+        if errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Parses the whole program even when it has syntax errors, returning every error along with
+    /// the statements that could be parsed. Declarations containing an error are left out.
+    pub fn parse_recovering(mut self) -> (Vec<Stmt>, Vec<LoxError>) {
+        let mut statements = vec![];
+        while !self.tokens.is_at_end() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+        (statements, self.errors)
+    }
+
+    // A syntax error abandons the declaration it occurs in: the error is recorded and parsing
+    // resumes at the next statement boundary.
+    fn declaration(&mut self) -> Option<Stmt> {
+        match self.try_declaration() {
+            Ok(statement) => Some(statement),
+            Err(error) => {
+                self.errors.push(error);
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    // Skips tokens until right after a `;` or right before a keyword starting a statement.
+    fn synchronize(&mut self) {
+        self.tokens.advance();
+        while !self.tokens.is_at_end() {
+            if self.tokens.previous().token == Token::SemiColon {
+                return;
+            }
+            match self.tokens.peek().token {
+                Token::Class
+                | Token::Fun
+                | Token::Var
+                | Token::For
+                | Token::If
+                | Token::While
+                | Token::Print
+                | Token::Return => return,
+                _ => {
+                    self.tokens.advance();
+                }
+            }
+        }
+    }
+
+    // declaration → classDecl | funDecl | varDecl | statement ;
+    fn try_declaration(&mut self) -> Result<Stmt, LoxError> {
+        if self.tokens.match_any(&[Token::Class]) {
+            return self.class_declaration();
+        }
+        if self.tokens.match_any(&[Token::Fun]) {
+            return Ok(Stmt::Function(self.function("function")?));
+        }
+        if self.tokens.match_any(&[Token::Var]) {
+            return self.var_declaration();
+        }
+        self.statement()
+    }
+
+    // classDecl → "class" IDENTIFIER ( "<" IDENTIFIER )? "{" function* "}" ;
+    fn class_declaration(&mut self) -> Result<Stmt, LoxError> {
+        let name = self.consume_identifier("Expect class name.")?;
+
+        let superclass = if self.tokens.match_any(&[Token::Lesser]) {
+            let superclass_name = self.consume_identifier("Expect superclass name.")?;
+            let span = superclass_name.span;
+            Some(Expr::new(
+                ExprKind::Variable(ExprId::next(), superclass_name),
+                span,
+            ))
+        } else {
+            None
+        };
+
+        self.consume(Token::LeftBrace, "Expect '{' before class body.")?;
+
+        let mut methods = vec![];
+        while !self.tokens.check(&Token::RightBrace) && !self.tokens.is_at_end() {
+            methods.push(self.function("method")?);
+        }
+        self.consume(Token::RightBrace, "Expect '}' after class body.")?;
+
+        Ok(Stmt::Class(name, superclass, methods))
+    }
+
+    // function → IDENTIFIER "(" parameters? ")" block ;
+    fn function(&mut self, kind: &str) -> Result<Rc<FunctionDecl>, LoxError> {
+        let name = self.consume_identifier(&format!("Expect {} name.", kind))?;
+        self.consume(
+            Token::LeftParen,
+            &format!("Expect '(' after {} name.", kind),
+        )?;
+
+        let mut params = vec![];
+        if !self.tokens.check(&Token::RightParen) {
+            loop {
+                // Reported without abandoning the declaration, which is still well-formed.
+                if params.len() == MAX_ARGUMENTS {
+                    let error = self.error(
+                        self.tokens.peek(),
+                        ErrorCode::TooManyParameters,
+                        "Can't have more than 255 parameters.",
+                    );
+                    self.errors.push(error);
+                }
+                params.push(self.consume_identifier("Expect parameter name.")?);
+                if !self.tokens.match_any(&[Token::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(Token::RightParen, "Expect ')' after parameters.")?;
+
+        self.consume(
+            Token::LeftBrace,
+            &format!("Expect '{{' before {} body.", kind),
+        )?;
+        let body = self.block()?;
+
+        Ok(Rc::new(FunctionDecl { name, params, body }))
+    }
+
+    // varDecl → "var" IDENTIFIER ( "=" expression )? ";" ;
+    fn var_declaration(&mut self) -> Result<Stmt, LoxError> {
+        let name = self.consume_identifier("Expect variable name.")?;
+        let initializer = if self.tokens.match_any(&[Token::Equal]) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume(Token::SemiColon, "Expect ';' after variable declaration.")?;
+        Ok(Stmt::Var(name, initializer))
+    }
+
+    // statement → exprStmt | forStmt | ifStmt | printStmt | returnStmt | whileStmt | block ;
+    fn statement(&mut self) -> Result<Stmt, LoxError> {
+        if self.tokens.match_any(&[Token::For]) {
+            return self.for_statement();
+        }
+        if self.tokens.match_any(&[Token::If]) {
+            return self.if_statement();
+        }
+        if self.tokens.match_any(&[Token::Print]) {
+            return self.print_statement();
+        }
+        if self.tokens.match_any(&[Token::Return]) {
+            return self.return_statement();
+        }
+        if self.tokens.match_any(&[Token::While]) {
+            return self.while_statement();
+        }
+        if self.tokens.match_any(&[Token::LeftBrace]) {
+            return Ok(Stmt::Block(self.block()?));
+        }
+        self.expression_statement()
+    }
+
+    // forStmt → "for" "(" ( varDecl | exprStmt | ";" ) expression? ";" expression? ")" statement ;
+    //
+    // There is no dedicated loop node: the clauses are desugared into a `while` wrapped in blocks.
+    fn for_statement(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.tokens.previous().clone();
+        self.consume(Token::LeftParen, "Expect '(' after 'for'.")?;
+
+        let initializer = if self.tokens.match_any(&[Token::SemiColon]) {
+            None
+        } else if self.tokens.match_any(&[Token::Var]) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let condition = if !self.tokens.check(&Token::SemiColon) {
+            self.expression()?
+        } else {
+            let span = keyword.span;
+            Expr::new(
+                ExprKind::Literal(Item::new("true".to_string(), Token::True, span)),
+                span,
+            )
+        };
+        self.consume(Token::SemiColon, "Expect ';' after loop condition.")?;
+
+        let increment = if !self.tokens.check(&Token::RightParen) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume(Token::RightParen, "Expect ')' after for clauses.")?;
+
+        let mut body = self.statement()?;
+        if let Some(increment) = increment {
+            body = Stmt::Block(vec![body, Stmt::Expression(increment)]);
+        }
+        body = Stmt::While(condition, Box::new(body));
+        if let Some(initializer) = initializer {
+            body = Stmt::Block(vec![initializer, body]);
+        }
+        Ok(body)
+    }
+
+    // ifStmt → "if" "(" expression ")" statement ( "else" statement )? ;
+    fn if_statement(&mut self) -> Result<Stmt, LoxError> {
+        self.consume(Token::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(Token::RightParen, "Expect ')' after if condition.")?;
+
+        let then_branch = Box::new(self.statement()?);
+        let else_branch = if self.tokens.match_any(&[Token::Else]) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+        Ok(Stmt::If(condition, then_branch, else_branch))
+    }
+
+    // printStmt → "print" expression ";" ;
+    fn print_statement(&mut self) -> Result<Stmt, LoxError> {
+        let value = self.expression()?;
+        self.consume(Token::SemiColon, "Expect ';' after value.")?;
+        Ok(Stmt::Print(value))
+    }
+
+    // returnStmt → "return" expression? ";" ;
+    fn return_statement(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.tokens.previous().clone();
+        let value = if !self.tokens.check(&Token::SemiColon) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume(Token::SemiColon, "Expect ';' after return value.")?;
+        Ok(Stmt::Return(keyword, value))
+    }
+
+    // whileStmt → "while" "(" expression ")" statement ;
+    fn while_statement(&mut self) -> Result<Stmt, LoxError> {
+        self.consume(Token::LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(Token::RightParen, "Expect ')' after condition.")?;
+        let body = self.statement()?;
+        Ok(Stmt::While(condition, Box::new(body)))
+    }
+
+    // block → "{" declaration* "}" ;
+    fn block(&mut self) -> Result<Vec<Stmt>, LoxError> {
+        let mut statements = vec![];
+        while !self.tokens.check(&Token::RightBrace) && !self.tokens.is_at_end() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+        self.consume(Token::RightBrace, "Expect '}' after block.")?;
+        Ok(statements)
+    }
+
+    // exprStmt → expression ";" ;
+    fn expression_statement(&mut self) -> Result<Stmt, LoxError> {
+        let expr = self.expression()?;
+        if !(self.trailing_expression && self.tokens.is_at_end()) {
+            self.consume(Token::SemiColon, "Expect ';' after expression.")?;
+        }
+        Ok(Stmt::Expression(expr))
+    }
+
+    // expression → assignment ;
+    fn expression(&mut self) -> Result<Expr, LoxError> {
+        self.assignment()
+    }
+
+    // assignment → ( call "." )? IDENTIFIER "=" assignment | logic_or ;
+    fn assignment(&mut self) -> Result<Expr, LoxError> {
+        let expr = self.or()?;
+
+        if self.tokens.match_any(&[Token::Equal]) {
+            let equals = self.tokens.previous().clone();
+            let value = self.assignment()?;
+
+            let span = expr.span.to(&value.span);
+            let kind = match expr.kind {
+                ExprKind::Variable(_, name) => {
+                    ExprKind::Assign(ExprId::next(), name, Box::new(value))
+                }
+                ExprKind::Get(object, name) => ExprKind::Set(object, name, Box::new(value)),
+                // The parser is not confused, so the target is kept and parsing goes on.
+                kind => {
+                    let error = self.error(
+                        &equals,
+                        ErrorCode::InvalidAssignmentTarget,
+                        "Invalid assignment target.",
+                    );
+                    self.errors.push(error);
+                    return Ok(Expr::new(kind, expr.span));
+                }
+            };
+            return Ok(Expr::new(kind, span));
+        }
+
+        Ok(expr)
+    }
+
+    // logic_or → logic_and ( "or" logic_and )* ;
+    fn or(&mut self) -> Result<Expr, LoxError> {
+        let mut expr = self.and()?;
+        while self.tokens.match_any(&[Token::Or]) {
+            let operator = self.tokens.previous().clone();
+            let right = self.and()?;
+            let span = expr.span.to(&right.span);
+            expr = Expr::new(
+                ExprKind::Logical(Box::new(expr), operator, Box::new(right)),
+                span,
+            );
+        }
+        Ok(expr)
+    }
+
+    // logic_and → equality ( "and" equality )* ;
+    fn and(&mut self) -> Result<Expr, LoxError> {
+        let mut expr = self.equality()?;
+        while self.tokens.match_any(&[Token::And]) {
+            let operator = self.tokens.previous().clone();
+            let right = self.equality()?;
+            let span = expr.span.to(&right.span);
+            expr = Expr::new(
+                ExprKind::Logical(Box::new(expr), operator, Box::new(right)),
+                span,
+            );
+        }
+        Ok(expr)
+    }
+
+    // equality → comparison ( ( "!=" | "==" ) comparison )* ;
+    fn equality(&mut self) -> Result<Expr, LoxError> {
+        let mut expr = self.comparison()?;
+        while self
+            .tokens
+            .match_any(&[Token::BangEqual, Token::EqualEqual])
+        {
+            let operator = self.tokens.previous().clone();
+            let right = self.comparison()?;
+            let span = expr.span.to(&right.span);
+            expr = Expr::new(
+                ExprKind::Binary(Box::new(expr), operator, Box::new(right)),
+                span,
+            );
+        }
+        Ok(expr)
+    }
+
+    // comparison → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
+    fn comparison(&mut self) -> Result<Expr, LoxError> {
+        let mut expr = self.term()?;
+        while self.tokens.match_any(&[
+            Token::Greater,
+            Token::GreaterEqual,
+            Token::Lesser,
+            Token::LesserEqual,
+        ]) {
+            let operator = self.tokens.previous().clone();
+            let right = self.term()?;
+            let span = expr.span.to(&right.span);
+            expr = Expr::new(
+                ExprKind::Binary(Box::new(expr), operator, Box::new(right)),
+                span,
+            );
+        }
+        Ok(expr)
+    }
+
+    // term → factor ( ( "-" | "+" ) factor )* ;
+    fn term(&mut self) -> Result<Expr, LoxError> {
+        let mut expr = self.factor()?;
+        while self.tokens.match_any(&[Token::Minus, Token::Plus]) {
+            let operator = self.tokens.previous().clone();
+            let right = self.factor()?;
+            let span = expr.span.to(&right.span);
+            expr = Expr::new(
+                ExprKind::Binary(Box::new(expr), operator, Box::new(right)),
+                span,
+            );
+        }
+        Ok(expr)
+    }
+
+    // factor → unary ( ( "/" | "*" ) unary )* ;
+    fn factor(&mut self) -> Result<Expr, LoxError> {
+        let mut expr = self.unary()?;
+        while self.tokens.match_any(&[Token::Slash, Token::Star]) {
+            let operator = self.tokens.previous().clone();
+            let right = self.unary()?;
+            let span = expr.span.to(&right.span);
+            expr = Expr::new(
+                ExprKind::Binary(Box::new(expr), operator, Box::new(right)),
+                span,
+            );
+        }
+        Ok(expr)
+    }
+
+    // unary → ( "!" | "-" ) unary | call ;
+    fn unary(&mut self) -> Result<Expr, LoxError> {
+        if self.tokens.match_any(&[Token::Bang, Token::Minus]) {
+            let operator = self.tokens.previous().clone();
+            let right = self.unary()?;
+            let span = operator.span.to(&right.span);
+            return Ok(Expr::new(ExprKind::Unary(operator, Box::new(right)), span));
+        }
+        self.call()
+    }
+
+    // call → primary ( "(" arguments? ")" | "." IDENTIFIER )* ;
+    fn call(&mut self) -> Result<Expr, LoxError> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.tokens.match_any(&[Token::LeftParen]) {
+                expr = self.finish_call(expr)?;
+            } else if self.tokens.match_any(&[Token::Dot]) {
+                let name = self.consume_identifier("Expect property name after '.'.")?;
+                let span = expr.span.to(&name.span);
+                expr = Expr::new(ExprKind::Get(Box::new(expr), name), span);
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+
+    // arguments → expression ( "," expression )* ;
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, LoxError> {
+        let mut arguments = vec![];
+        if !self.tokens.check(&Token::RightParen) {
+            loop {
+                if arguments.len() == MAX_ARGUMENTS {
+                    let error = self.error(
+                        self.tokens.peek(),
+                        ErrorCode::TooManyArguments,
+                        "Can't have more than 255 arguments.",
+                    );
+                    self.errors.push(error);
+                }
+                arguments.push(self.expression()?);
+                if !self.tokens.match_any(&[Token::Comma]) {
+                    break;
+                }
+            }
+        }
+        let paren = self
+            .consume(Token::RightParen, "Expect ')' after arguments.")?
+            .clone();
+        let span = callee.span.to(&paren.span);
+        Ok(Expr::new(
+            ExprKind::Call(Box::new(callee), paren, arguments),
+            span,
+        ))
+    }
+
+    // primary → NUMBER | STRING | "true" | "false" | "nil" | "this" | IDENTIFIER
+    //         | "(" expression ")" | "super" "." IDENTIFIER ;
+    fn primary(&mut self) -> Result<Expr, LoxError> {
+        let item = self.tokens.peek().clone();
+        match item.token {
+            Token::False | Token::True | Token::Nil | Token::Number(_) | Token::String(_) => {
+                self.tokens.advance();
+                Ok(Expr::new(ExprKind::Literal(item.clone()), item.span))
+            }
+            Token::This => {
+                self.tokens.advance();
+                Ok(Expr::new(
+                    ExprKind::This(ExprId::next(), item.clone()),
+                    item.span,
+                ))
+            }
+            Token::Super => {
+                self.tokens.advance();
+                self.consume(Token::Dot, "Expect '.' after 'super'.")?;
+                let method = self.consume_identifier("Expect superclass method name.")?;
+                let span = item.span.to(&method.span);
+                Ok(Expr::new(
+                    ExprKind::Super(ExprId::next(), item, method),
+                    span,
+                ))
+            }
+            Token::Identifier(_) => {
+                self.tokens.advance();
+                Ok(Expr::new(
+                    ExprKind::Variable(ExprId::next(), item.clone()),
+                    item.span,
+                ))
+            }
+            Token::LeftParen => {
+                self.tokens.advance();
+                let expr = self.expression()?;
+                let paren = self.consume(Token::RightParen, "Expect ')' after expression.")?;
+                let span = item.span.to(&paren.span);
+                Ok(Expr::new(ExprKind::Grouping(Box::new(expr)), span))
+            }
+            _ => Err(self.error(&item, ErrorCode::ExpectedExpression, "Expect expression.")),
+        }
+    }
+
+    fn consume(&mut self, token: Token, message: &str) -> Result<&Item, LoxError> {
+        if self.tokens.check(&token) {
+            return Ok(self.tokens.advance());
+        }
+        Err(self.error(self.tokens.peek(), ErrorCode::ExpectedToken, message))
+    }
+
+    fn consume_identifier(&mut self, message: &str) -> Result<Item, LoxError> {
+        match self.tokens.peek().token {
+            Token::Identifier(_) => Ok(self.tokens.advance().clone()),
+            _ => Err(self.error(self.tokens.peek(), ErrorCode::ExpectedToken, message)),
+        }
+    }
+
+    fn error(&self, item: &Item, code: ErrorCode, message: &str) -> LoxError {
+        LoxError::new(code, message, item.span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::{ErrorCode, LoxError};
+    use crate::expr::{Visitor, VisitorPrinter};
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+    use crate::span::Span;
+    use crate::stmt::Stmt;
+
+    fn parse_recovering(source: &str) -> (String, Vec<String>) {
+        let tokens = Scanner::new(source).scan_tokens().expect("Failed scanning");
+        let (statements, errors) = Parser::new(tokens).parse_recovering();
+        let mut printer = VisitorPrinter::new();
+        let printed = statements
+            .iter()
+            .map(|statement| printer.print_stmt(statement))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let reported = errors.iter().map(|error| error.brief(source)).collect();
+        (printed, reported)
+    }
+
+    fn parse(source: &str) -> Result<Vec<Stmt>, Vec<LoxError>> {
+        let tokens = Scanner::new(source).scan_tokens().expect("Failed scanning");
+        Parser::new(tokens).parse()
+    }
+
+    fn print(source: &str) -> String {
+        let statements = parse(source).expect("Failed parsing");
+        let mut printer = VisitorPrinter::new();
+        statements
+            .iter()
+            .map(|statement| printer.print_stmt(statement))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn parse_precedence() {
+        assert_eq!("(; (+ 1 (* 2 3)))", print("1 + 2 * 3;"));
+        assert_eq!("(; (== (< 1 2) true))", print("1 < 2 == true;"));
+        assert_eq!("(; (- (- 1 2) 3))", print("1 - 2 - 3;"));
+        assert_eq!("(; (* (- 1) (group (+ 2 3))))", print("-1 * (2 + 3);"));
+        assert_eq!("(; (! (! false)))", print("!!false;"));
+        assert_eq!("(; (or a (and b c)))", print("a or b and c;"));
+        assert_eq!("(; (= a (= b 1)))", print("a = b = 1;"));
+        assert_eq!("(; (call (call f 1 (+ 2 3))))", print("f(1, 2 + 3)();"));
+    }
+
+    #[test]
+    fn parse_statements() {
+        assert_eq!("(var a = 1) (print a)", print("var a = 1; print a;"));
+        assert_eq!(
+            "(if a (block (print 1)) (print 2))",
+            print("if (a) { print 1; } else print 2;")
+        );
+        assert_eq!(
+            "(block (var i = 0) (while (< i 3) (block (print i) (; (= i (+ i 1))))))",
+            print("for (var i = 0; i < 3; i = i + 1) print i;")
+        );
+        assert_eq!("(while true (print 1))", print("for (;;) print 1;"));
+        assert_eq!(
+            "(fun add (a b) (return (+ a b)))",
+            print("fun add(a, b) { return a + b; }")
+        );
+        assert_eq!(
+            "(class Foo (method bar () (return)))",
+            print("class Foo { bar() { return; } }")
+        );
+        assert_eq!(
+            "(class B < A (method init (x) (; (= this x x)) (; (call (super init)))))",
+            print("class B < A { init(x) { this.x = x; super.init(); } }")
+        );
+        assert_eq!("(; (= (. a b) c (. d e)))", print("a.b.c = d.e;"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error_in = |source: &str| {
+            let errors = parse(source).unwrap_err();
+            assert_eq!(1, errors.len());
+            (errors[0].code, errors[0].brief(source))
+        };
+        assert_eq!(
+            (
+                ErrorCode::ExpectedToken,
+                "[line 1] Error at ';': Expect ')' after expression.".to_string()
+            ),
+            error_in("(1 + 2;")
+        );
+        assert_eq!(
+            (
+                ErrorCode::ExpectedExpression,
+                "[line 1] Error at ')': Expect expression.".to_string()
+            ),
+            error_in("1 + );")
+        );
+        assert_eq!(
+            (
+                ErrorCode::ExpectedToken,
+                "[line 1] Error at end: Expect ';' after value.".to_string()
+            ),
+            error_in("print 1")
+        );
+        assert_eq!(
+            (
+                ErrorCode::InvalidAssignmentTarget,
+                "[line 1] Error at '=': Invalid assignment target.".to_string()
+            ),
+            error_in("1 + 2 = 3;")
+        );
+    }
+
+    #[test]
+    fn parse_spans() {
+        let expression_span = |source: &str| -> Span {
+            match parse(source).expect("Failed parsing").remove(0) {
+                Stmt::Expression(expr) => expr.span,
+                _ => panic!("Expected an expression statement"),
+            }
+        };
+        let columns = |span: Span| (span.start.column, span.end.column);
+
+        assert_eq!((1, 10), columns(expression_span("1 + 2 * 3;")));
+        assert_eq!((1, 7), columns(expression_span("-(a.b);")));
+        assert_eq!((1, 11), columns(expression_span("f(1, 2)(3);")));
+        assert_eq!((1, 14), columns(expression_span("a.b = super.c;")));
+
+        let span = expression_span("a\n  or\n  b;");
+        assert_eq!((1, 3), (span.start.line, span.end.line));
+        assert_eq!((0, 10), (span.start.offset, span.end.offset));
+    }
+
+    #[test]
+    fn parse_recovers_at_statement_boundaries() {
+        let (statements, errors) =
+            parse_recovering("var = 1; print 2;\nfun (a) {} print 3;\nclass A { m( }\nprint 4;");
+        assert_eq!("(print 2) (print 3) (print 4)", statements);
+        assert_eq!(
+            vec![
+                "[line 1] Error at '=': Expect variable name.",
+                "[line 2] Error at '(': Expect function name.",
+                "[line 3] Error at '}': Expect parameter name.",
+            ],
+            errors
+        );
+
+        let (statements, errors) = parse_recovering("{ print 1 print 2; } print 3;");
+        assert_eq!("(block) (print 3)", statements);
+        assert_eq!(
+            vec!["[line 1] Error at 'print': Expect ';' after value."],
+            errors
+        );
+    }
+
+    #[test]
+    fn parse_reports_errors_without_abandoning_statements() {
+        let (statements, errors) = parse_recovering("1 = 2; a + b = c; print 3;");
+        assert_eq!("(; 1) (; (+ a b)) (print 3)", statements);
+        assert_eq!(
+            vec![
+                "[line 1] Error at '=': Invalid assignment target.",
+                "[line 1] Error at '=': Invalid assignment target.",
+            ],
+            errors
+        );
+
+        let arguments = vec!["1"; 257].join(", ");
+        let (_, errors) = parse_recovering(&format!("f({});", arguments));
+        assert_eq!(
+            vec!["[line 1] Error at '1': Can't have more than 255 arguments."],
+            errors
+        );
+    }
+
+    #[test]
+    fn parse_trailing_expression() {
+        let parse_interactive = |source: &str| {
+            let tokens = Scanner::new(source).scan_tokens().expect("Failed scanning");
+            Parser::new(tokens).with_trailing_expression().parse()
+        };
+        assert!(parse_interactive("var a = 1; a + 2").is_ok());
+        assert!(parse_interactive("a + 2;").is_ok());
+        assert!(parse_interactive("a + 2 print a;").is_err());
+        assert!(parse_interactive("print a").is_err());
+        assert!(parse("a + 2").is_err());
+    }
+}
@@ -0,0 +1,457 @@
+use crate::errors::{ErrorCode, LoxError};
+use crate::expr::{self, Expr, ExprId, ExprKind};
+use crate::span::Span;
+use crate::stmt::{self, FunctionDecl, Stmt};
+use crate::token::Item;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionKind {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassKind {
+    None,
+    Class,
+    Subclass,
+}
+
+struct Binding {
+    // `false` while the variable's initializer is being resolved.
+    defined: bool,
+    span: Span,
+}
+
+/// Static pass run between parsing and interpretation. It binds every local variable reference
+/// to the number of scopes between the use and its declaration, and reports the scoping errors
+/// that can be caught before running anything.
+pub struct Resolver {
+    // Innermost scope last.
+    scopes: Vec<HashMap<String, Binding>>,
+    locals: HashMap<ExprId, usize>,
+    current_function: FunctionKind,
+    current_class: ClassKind,
+    errors: Vec<LoxError>,
+}
+
+impl Resolver {
+    pub fn resolve(mut self, statements: &[Stmt]) -> Result<HashMap<ExprId, usize>, Vec<LoxError>> {
+        self.resolve_statements(statements);
+        if self.errors.is_empty() {
+            Ok(self.locals)
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn resolve_statements(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            self.resolve_stmt(statement);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
+        stmt::Visitor::visit_stmt(self, stmt)
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        expr::Visitor::visit_expr(self, expr)
+    }
+
+    fn resolve_function(&mut self, declaration: &FunctionDecl, kind: FunctionKind) {
+        let enclosing_function = self.current_function;
+        self.current_function = kind;
+
+        self.begin_scope();
+        for param in declaration.params.iter() {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_statements(&declaration.body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+    }
+
+    fn resolve_local(&mut self, id: ExprId, name: &Item) {
+        for (distance, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(&name.lexeme) {
+                self.locals.insert(id, distance);
+                return;
+            }
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Item) {
+        let binding = Binding {
+            defined: false,
+            span: name.span,
+        };
+        let previous = match self.scopes.last_mut() {
+            Some(scope) => scope.insert(name.lexeme.clone(), binding),
+            None => None,
+        };
+        if let Some(previous) = previous {
+            let error = LoxError::new(
+                ErrorCode::AlreadyDeclared,
+                "Already a variable with this name in this scope.",
+                name.span,
+            )
+            .with_label(previous.span, "previously declared here");
+            self.errors.push(error);
+        }
+    }
+
+    fn define(&mut self, name: &Item) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(
+                name.lexeme.clone(),
+                Binding {
+                    defined: true,
+                    span: name.span,
+                },
+            );
+        }
+    }
+
+    fn define_keyword(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(
+                name.to_string(),
+                Binding {
+                    defined: true,
+                    span: Span::default(),
+                },
+            );
+        }
+    }
+
+    fn error(&mut self, item: &Item, code: ErrorCode, message: &str) {
+        self.errors.push(LoxError::new(code, message, item.span));
+    }
+}
+
+impl expr::Visitor<()> for Resolver {
+    fn new() -> Self {
+        Resolver {
+            scopes: vec![],
+            locals: HashMap::new(),
+            current_function: FunctionKind::None,
+            current_class: ClassKind::None,
+            errors: vec![],
+        }
+    }
+
+    fn visit_expr_unary(&mut self, _operator: &Item, expr: &Expr) {
+        self.resolve_expr(expr);
+    }
+
+    fn visit_expr_binary(&mut self, left: &Expr, _operator: &Item, right: &Expr) {
+        self.resolve_expr(left);
+        self.resolve_expr(right);
+    }
+
+    fn visit_expr_grouping(&mut self, expr: &Expr) {
+        self.resolve_expr(expr);
+    }
+
+    fn visit_expr_literal(&mut self, _value: &Item) {}
+
+    fn visit_expr_logical(&mut self, left: &Expr, _operator: &Item, right: &Expr) {
+        self.resolve_expr(left);
+        self.resolve_expr(right);
+    }
+
+    fn visit_expr_variable(&mut self, id: ExprId, name: &Item) {
+        let in_own_initializer = self
+            .scopes
+            .last()
+            .and_then(|scope| scope.get(&name.lexeme))
+            .is_some_and(|binding| !binding.defined);
+        if in_own_initializer {
+            self.error(
+                name,
+                ErrorCode::ReadInOwnInitializer,
+                "Can't read local variable in its own initializer.",
+            );
+        }
+        self.resolve_local(id, name);
+    }
+
+    fn visit_expr_assign(&mut self, id: ExprId, name: &Item, value: &Expr) {
+        self.resolve_expr(value);
+        self.resolve_local(id, name);
+    }
+
+    fn visit_expr_call(&mut self, callee: &Expr, _paren: &Item, arguments: &[Expr]) {
+        self.resolve_expr(callee);
+        for argument in arguments {
+            self.resolve_expr(argument);
+        }
+    }
+
+    fn visit_expr_get(&mut self, object: &Expr, _name: &Item) {
+        self.resolve_expr(object);
+    }
+
+    fn visit_expr_set(&mut self, object: &Expr, _name: &Item, value: &Expr) {
+        self.resolve_expr(value);
+        self.resolve_expr(object);
+    }
+
+    fn visit_expr_this(&mut self, id: ExprId, keyword: &Item) {
+        if self.current_class == ClassKind::None {
+            self.error(
+                keyword,
+                ErrorCode::ThisOutsideClass,
+                "Can't use 'this' outside of a class.",
+            );
+            return;
+        }
+        self.resolve_local(id, keyword);
+    }
+
+    fn visit_expr_super(&mut self, id: ExprId, keyword: &Item, _method: &Item) {
+        match self.current_class {
+            ClassKind::None => self.error(
+                keyword,
+                ErrorCode::SuperOutsideClass,
+                "Can't use 'super' outside of a class.",
+            ),
+            ClassKind::Class => self.error(
+                keyword,
+                ErrorCode::SuperWithoutSuperclass,
+                "Can't use 'super' in a class with no superclass.",
+            ),
+            ClassKind::Subclass => self.resolve_local(id, keyword),
+        }
+    }
+}
+
+impl stmt::Visitor<()> for Resolver {
+    fn visit_stmt_expression(&mut self, expr: &Expr) {
+        self.resolve_expr(expr);
+    }
+
+    fn visit_stmt_print(&mut self, expr: &Expr) {
+        self.resolve_expr(expr);
+    }
+
+    fn visit_stmt_var(&mut self, name: &Item, initializer: Option<&Expr>) {
+        self.declare(name);
+        if let Some(initializer) = initializer {
+            self.resolve_expr(initializer);
+        }
+        self.define(name);
+    }
+
+    fn visit_stmt_block(&mut self, statements: &[Stmt]) {
+        self.begin_scope();
+        self.resolve_statements(statements);
+        self.end_scope();
+    }
+
+    fn visit_stmt_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: Option<&Stmt>) {
+        self.resolve_expr(condition);
+        self.resolve_stmt(then_branch);
+        if let Some(else_branch) = else_branch {
+            self.resolve_stmt(else_branch);
+        }
+    }
+
+    fn visit_stmt_while(&mut self, condition: &Expr, body: &Stmt) {
+        self.resolve_expr(condition);
+        self.resolve_stmt(body);
+    }
+
+    fn visit_stmt_function(&mut self, declaration: &Rc<FunctionDecl>) {
+        // Defined eagerly so that the function can refer to itself recursively.
+        self.declare(&declaration.name);
+        self.define(&declaration.name);
+        self.resolve_function(declaration, FunctionKind::Function);
+    }
+
+    fn visit_stmt_return(&mut self, keyword: &Item, value: Option<&Expr>) {
+        if self.current_function == FunctionKind::None {
+            self.error(
+                keyword,
+                ErrorCode::TopLevelReturn,
+                "Can't return from top-level code.",
+            );
+        }
+        if let Some(value) = value {
+            if self.current_function == FunctionKind::Initializer {
+                self.error(
+                    keyword,
+                    ErrorCode::ReturnFromInitializer,
+                    "Can't return a value from an initializer.",
+                );
+            }
+            self.resolve_expr(value);
+        }
+    }
+
+    fn visit_stmt_class(
+        &mut self,
+        name: &Item,
+        superclass: Option<&Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) {
+        let enclosing_class = self.current_class;
+        self.current_class = ClassKind::Class;
+
+        self.declare(name);
+        self.define(name);
+
+        if let Some(superclass) = superclass {
+            if let ExprKind::Variable(_, superclass_name) = &superclass.kind {
+                if superclass_name.lexeme == name.lexeme {
+                    self.error(
+                        superclass_name,
+                        ErrorCode::InheritFromSelf,
+                        "A class can't inherit from itself.",
+                    );
+                }
+            }
+            self.current_class = ClassKind::Subclass;
+            self.resolve_expr(superclass);
+
+            self.begin_scope();
+            self.define_keyword("super");
+        }
+
+        self.begin_scope();
+        self.define_keyword("this");
+        for method in methods {
+            let kind = if method.name.lexeme == "init" {
+                FunctionKind::Initializer
+            } else {
+                FunctionKind::Method
+            };
+            self.resolve_function(method, kind);
+        }
+        self.end_scope();
+
+        if superclass.is_some() {
+            self.end_scope();
+        }
+
+        self.current_class = enclosing_class;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::{ErrorCode, Label, LoxError};
+    use crate::expr::Visitor;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+    use crate::span::SourceMap;
+
+    fn resolve(source: &str) -> Vec<LoxError> {
+        let statements = Parser::new(Scanner::new(source).scan_tokens().expect("Failed scanning"))
+            .parse()
+            .expect("Failed parsing");
+        Resolver::new()
+            .resolve(&statements)
+            .err()
+            .unwrap_or_default()
+    }
+
+    fn resolve_errors(source: &str) -> Vec<String> {
+        resolve(source)
+            .iter()
+            .map(|error| error.brief(source))
+            .collect()
+    }
+
+    #[test]
+    fn resolve_valid_programs() {
+        assert!(resolve_errors("var a = 1; { var b = a; { var a = b; } }").is_empty());
+        assert!(resolve_errors("var a = 1; var a = a;").is_empty());
+        assert!(resolve_errors("fun f(n) { if (n > 0) return f(n - 1); }").is_empty());
+        assert!(resolve_errors(
+            "class A { init() { return; } } class B < A { m() { super.m(); } }"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn resolve_scoping_errors() {
+        assert_eq!(
+            vec!["[line 1] Error at 'a': Can't read local variable in its own initializer."],
+            resolve_errors("{ var a = a; }")
+        );
+        assert_eq!(
+            vec!["[line 1] Error at 'a': Already a variable with this name in this scope."],
+            resolve_errors("{ var a = 1; var a = 2; }")
+        );
+        assert_eq!(
+            vec!["[line 1] Error at 'a': Already a variable with this name in this scope."],
+            resolve_errors("fun f(a, a) {}")
+        );
+        assert_eq!(
+            vec!["[line 1] Error at 'return': Can't return from top-level code."],
+            resolve_errors("return 1;")
+        );
+        assert_eq!(
+            vec!["[line 1] Error at 'return': Can't return a value from an initializer."],
+            resolve_errors("class A { init() { return 1; } }")
+        );
+    }
+
+    #[test]
+    fn resolve_class_errors() {
+        assert_eq!(
+            vec!["[line 1] Error at 'this': Can't use 'this' outside of a class."],
+            resolve_errors("fun f() { return this; }")
+        );
+        assert_eq!(
+            vec!["[line 1] Error at 'super': Can't use 'super' outside of a class."],
+            resolve_errors("super.m();")
+        );
+        assert_eq!(
+            vec!["[line 1] Error at 'super': Can't use 'super' in a class with no superclass."],
+            resolve_errors("class A { m() { super.m(); } }")
+        );
+        assert_eq!(
+            vec!["[line 1] Error at 'A': A class can't inherit from itself."],
+            resolve_errors("class A < A {}")
+        );
+    }
+
+    #[test]
+    fn resolve_reports_every_error() {
+        assert_eq!(2, resolve_errors("return; { var a = a; }").len());
+    }
+
+    #[test]
+    fn resolve_labels_previous_declaration() {
+        let source = "{ var a = 1;\n  var a = 2; }";
+        let errors = resolve(source);
+        assert_eq!(1, errors.len());
+        assert_eq!(ErrorCode::AlreadyDeclared, errors[0].code);
+        assert_eq!(2, errors[0].line());
+        assert_eq!(
+            vec![Label {
+                span: SourceMap::new(source).span(6, 7),
+                message: "previously declared here".to_string(),
+            }],
+            errors[0].labels
+        );
+    }
+}
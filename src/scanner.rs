@@ -1,134 +1,147 @@
-// use errors::{ErrorKind, Result};
-// use literal::{Literal, Number};
+use crate::errors::{ErrorCode, LoxError};
+use crate::span::{Position, Span};
 use crate::token::{Item, Token};
-// use crate::errors::LoxError;
-// use token_type::TokenType;
 
 pub struct Scanner {
     characters: Vec<char>,
+    // Byte offset of every character, plus the length of the source as a final entry.
+    offsets: Vec<usize>,
     current: usize,
     start: usize,
     line: usize,
+    line_start: usize,
+    start_position: Position,
 }
 
 impl Scanner {
     pub fn new(source: &str) -> Scanner {
         let source: String = source.into();
         let characters = source.chars().collect::<Vec<_>>();
+        let offsets = source
+            .char_indices()
+            .map(|(offset, _)| offset)
+            .chain(std::iter::once(source.len()))
+            .collect();
         Scanner {
             characters,
+            offsets,
             current: 0,
             start: 0,
             line: 1,
+            line_start: 0,
+            start_position: Position::default(),
         }
     }
 
-    pub fn scan_tokens(mut self) -> Vec<Item> {
+    /// Scans the whole source. A bad character does not stop the scan: every lexical error is
+    /// collected and returned together once the end of input is reached. On success the last
+    /// item is always `Token::Eof`, positioned just past the final character.
+    pub fn scan_tokens(mut self) -> Result<Vec<Item>, Vec<LoxError>> {
         let mut tokens = vec![];
+        let mut errors = vec![];
         while self.check_done_scanning() {
             self.start = self.current;
-            let token = self.scan_token().expect("Whoopsie");
-            if let Some(token) = token {
-                tokens.push(token);
-            } 
+            self.start_position = self.position();
+            match self.scan_token() {
+                Ok(Some(token)) => tokens.push(token),
+                Ok(None) => {}
+                Err(error) => errors.push(error),
+            }
+        }
+
+        let end = self.position();
+        tokens.push(Item::new(String::new(), Token::Eof, Span::new(end, end)));
+
+        if errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(errors)
         }
-        tokens
     }
 
-    fn scan_token(&mut self) -> Result<Option<Item>, String> {
+    fn scan_token(&mut self) -> Result<Option<Item>, LoxError> {
         let character = self.characters[self.current];
         self.current += 1;
         Ok(match character {
-            '(' => Some(Item::new(character.to_string(), Token::LeftParen, self.line)),
-            ')' => Some(Item::new(character.to_string(), Token::RightParen, self.line)),
-            '{' => Some(Item::new(character.to_string(), Token::LeftBrace, self.line)),
-            '}' => Some(Item::new(character.to_string(), Token::RightBrace, self.line)),
-            ',' => Some(Item::new(character.to_string(), Token::Comma, self.line)),
-            '.' => Some(Item::new(character.to_string(), Token::Dot, self.line)),
-            '-' => Some(Item::new(character.to_string(), Token::Minus, self.line)),
-            '+' => Some(Item::new(character.to_string(), Token::Plus, self.line)),
-            ';' => Some(Item::new(character.to_string(), Token::SemiColon, self.line)),
-            '*' => Some(Item::new(character.to_string(), Token::Star, self.line)),
-
-            '=' => {
-                Some(if self.match_char('=') {
-                    self.make_token(Token::EqualEqual)
-                } else {
-                    self.make_token(Token::Equal)
-                })
-            },
-
-            '!' => {
-                Some(if self.match_char('=') {
-                    self.make_token(Token::BangEqual)
-                } else {
-                    self.make_token(Token::Bang)
-                })
-            },
-
-            '<' => {
-                Some(if self.match_char('=') {
-                    self.make_token(Token::LesserEqual)
-                } else {
-                    self.make_token(Token::Lesser)
-                })
-            },
-            '>' => {
-                Some(if self.match_char('=') {
-                    self.make_token(Token::GreaterEqual)
-                } else {
-                    self.make_token(Token::Greater)
-                })
-            },
+            '(' => Some(self.make_token(Token::LeftParen)),
+            ')' => Some(self.make_token(Token::RightParen)),
+            '{' => Some(self.make_token(Token::LeftBrace)),
+            '}' => Some(self.make_token(Token::RightBrace)),
+            ',' => Some(self.make_token(Token::Comma)),
+            '.' => Some(self.make_token(Token::Dot)),
+            '-' => Some(self.make_token(Token::Minus)),
+            '+' => Some(self.make_token(Token::Plus)),
+            ';' => Some(self.make_token(Token::SemiColon)),
+            '*' => Some(self.make_token(Token::Star)),
+
+            '=' => Some(if self.match_char('=') {
+                self.make_token(Token::EqualEqual)
+            } else {
+                self.make_token(Token::Equal)
+            }),
+
+            '!' => Some(if self.match_char('=') {
+                self.make_token(Token::BangEqual)
+            } else {
+                self.make_token(Token::Bang)
+            }),
+
+            '<' => Some(if self.match_char('=') {
+                self.make_token(Token::LesserEqual)
+            } else {
+                self.make_token(Token::Lesser)
+            }),
+            '>' => Some(if self.match_char('=') {
+                self.make_token(Token::GreaterEqual)
+            } else {
+                self.make_token(Token::Greater)
+            }),
 
             '/' => {
                 if self.match_char('/') {
-                    while self.characters[self.current] != '\n' && self.check_done_scanning() {
+                    while self.peek() != '\n' && self.check_done_scanning() {
                         self.current += 1;
                     }
                     None
                 } else {
                     Some(self.make_token(Token::Slash))
                 }
-            },
+            }
 
             '"' => Some(self.scan_string()?),
 
-            ' ' | '\r' | '\t' => {
+            ' ' | '\r' | '\t' => None,
+
+            '\n' => {
+                self.new_line();
                 None
-            },
+            }
 
-            digit if self.is_digit(digit) => {
-                Some(self.scan_number()?)
-            },
+            digit if self.is_digit(digit) => Some(self.scan_number()?),
 
-            alpha if self.is_alpha(alpha) => {
-                self.scan_ident()
-            },
+            alpha if self.is_alpha(alpha) => self.scan_ident(),
 
             _ => {
-                return Err(format!(
-                    "No matching token '{}' on line {}",
-                    character, self.line
-                ))
+                return Err(self.error(
+                    ErrorCode::UnexpectedCharacter,
+                    &format!("Unexpected character '{}'.", character),
+                ));
             }
-            // LoxError {
-            //     line: self.line,
-            //     message: String::from("no matching token")
-            // }
         })
     }
 
-    fn scan_string(&mut self) -> Result<Item, String> {
-        while self.characters[self.current] != '"' && self.check_done_scanning() {
-            if self.characters[self.current] == '\n' {
-                self.line += 1;
-            }
+    fn scan_string(&mut self) -> Result<Item, LoxError> {
+        while self.peek() != '"' && self.check_done_scanning() {
             self.current += 1;
+            if self.characters[self.current - 1] == '\n' {
+                self.new_line();
+            }
         }
 
         if !self.check_done_scanning() {
-            return Err(format!("Unterminated string literal at line {}", self.line));
+            return Err(self
+                .error(ErrorCode::UnterminatedString, "Unterminated string.")
+                .with_help("Close the string with '\"' before the end of the file."));
         }
 
         // the closing '"'
@@ -136,78 +149,62 @@ impl Scanner {
 
         let start = self.start + 1;
         let end = self.current - 1;
-        let val = self.characters[start..end].iter().cloned().collect::<String>();
+        let val = self.characters[start..end]
+            .iter()
+            .cloned()
+            .collect::<String>();
         Ok(self.make_token(Token::String(val)))
     }
 
-    fn scan_number(&mut self) -> Result<Item, String> {
-        let mut is_float = false;
-        while self.is_digit(self.characters[self.current]) {
+    fn scan_number(&mut self) -> Result<Item, LoxError> {
+        while self.is_digit(self.peek()) {
             self.current += 1;
         }
 
-        if self.characters[self.current] == '.' && self.is_digit(self.characters[self.current + 1]) {
-            is_float = true;
+        if self.peek() == '.' && self.is_digit(self.peek_next()) {
             // Consume the '.'
             self.current += 1;
 
-            while self.is_digit(self.characters[self.current]) {
+            while self.is_digit(self.peek()) {
                 self.current += 1;
             }
         }
 
-        let lexeme = self.characters[self.start..self.current].iter().cloned().collect::<String>();
-        Ok(self.make_token(Token::Number(if is_float {
-            let parsed: f64 = lexeme.parse().expect("Failed parsing to float");
-            // let literal = Number::Float(parsed);
-            parsed
-        } else {
-            let parsed: i64 = lexeme.parse().expect("Failed parsing to int");
-            // let literal = Number::Int(parsed);
-            parsed as f64
-        })))
+        let lexeme = self.characters[self.start..self.current]
+            .iter()
+            .cloned()
+            .collect::<String>();
+        // Lox only has doubles, so integer literals too large for any integer type still parse.
+        match lexeme.parse::<f64>() {
+            Ok(parsed) => Ok(self.make_token(Token::Number(parsed))),
+            Err(_) => Err(self.error(
+                ErrorCode::InvalidNumber,
+                &format!("Invalid number literal '{}'.", lexeme),
+            )),
+        }
     }
 
     fn scan_ident(&mut self) -> Option<Item> {
-        while self.is_alphanumeric(self.characters[self.current]) {
+        while self.is_alphanumeric(self.peek()) {
             self.current += 1;
         }
         use Token::*;
-        // let text = self.characters[self.start..self.current].iter().cloned().collect();
         let mut text = "".to_owned();
         for char in self.characters[self.start..self.current].iter() {
-            text.push_str(&char.to_string());
+            text.push(*char);
         }
 
-        let token = match &*text {
-            "and" => And,
-            "class" => Class,
-            "else" => Else,
-            "false" => False,
-            "fun" => Fun,
-            "for" => For,
-            "if" => If,
-            "nil" => Nil,
-            "or" => Or,
-            "print" => Print,
-            "return" => Return,
-            "super" => Super,
-            "this" => This,
-            "true" => True,
-            "var" => Var,
-            "while" => While,
-            _ => Identifier(text),
-        };
+        let token = Token::keyword(&text).unwrap_or(Identifier(text));
 
         Some(self.make_token(token))
     }
 
     fn is_digit(&self, ch: char) -> bool {
-        ch >= '0' && ch <= '9'
+        ch.is_ascii_digit()
     }
 
     fn is_alpha(&self, ch: char) -> bool {
-        (ch >= 'a' && ch <= 'z') || (ch >= 'A' && ch <= 'Z') || ch == '_'
+        ch.is_ascii_alphabetic() || ch == '_'
     }
 
     fn is_alphanumeric(&self, ch: char) -> bool {
@@ -215,7 +212,7 @@ impl Scanner {
     }
 
     fn match_char(&mut self, expected: char) -> bool {
-        if self.check_done_scanning() {
+        if !self.check_done_scanning() {
             return false;
         }
         if self.characters[self.current] != expected {
@@ -226,12 +223,55 @@ impl Scanner {
         true
     }
 
+    fn peek(&self) -> char {
+        if self.check_done_scanning() {
+            self.characters[self.current]
+        } else {
+            '\0'
+        }
+    }
+
+    fn peek_next(&self) -> char {
+        if self.current + 1 < self.characters.len() {
+            self.characters[self.current + 1]
+        } else {
+            '\0'
+        }
+    }
+
     fn make_token(&self, token: Token) -> Item {
         let lexeme = self.characters[self.start..self.current]
             .iter()
             .cloned()
             .collect::<String>();
-        Item::new(lexeme, token, self.line)
+        Item::new(
+            lexeme,
+            token,
+            Span::new(self.start_position, self.position()),
+        )
+    }
+
+    /// Must be called right after consuming a `'\n'`.
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.line_start = self.current;
+    }
+
+    fn position(&self) -> Position {
+        Position::new(
+            self.offsets[self.current],
+            self.line,
+            self.current - self.line_start + 1,
+        )
+    }
+
+    /// Reports an error covering the token scanned so far.
+    fn error(&self, code: ErrorCode, message: &str) -> LoxError {
+        LoxError::new(
+            code,
+            message,
+            Span::new(self.start_position, self.position()),
+        )
     }
 
     fn check_done_scanning(&self) -> bool {
@@ -239,237 +279,192 @@ impl Scanner {
     }
 }
 
-// impl Scanner {
-//     pub fn new(source: &str) -> Scanner {
-//         let source: String = source.into();
-//         let chars = source.chars().collect::<Vec<_>>();
-//         Scanner {
-//             characters: chars,
-//             start: 0,
-//             current: 0,
-//             line: 1,
-//         }
-//     }
-
-//     fn is_at_end(&self) -> bool {
-//         self.current >= self.characters.len()
-//     }
-
-//     pub fn scan(mut self) -> Result<Vec<Token>> {
-//         let mut tokens = vec![];
-
-//         while !self.is_at_end() {
-//             self.start = self.current;
-//             let token = self.scan_tokens()?;
-//             if let Some(token) = token {
-//                 tokens.push(token);
-//             }
-//         }
-
-//         tokens.push(Token::new(TokenType::Eof, "", None, self.line));
-//         Ok(tokens)
-//     }
-
-//     fn scan_tokens(&mut self) -> Result<Option<Token>> {
-//         let ch = self.next();
-//         Ok(match ch {
-//             // Single-char tokens
-//             '(' => Some(self.make_token(TokenType::LeftParen, None)),
-//             ')' => Some(self.make_token(TokenType::RightParen, None)),
-//             '{' => Some(self.make_token(TokenType::LeftBrace, None)),
-//             '}' => Some(self.make_token(TokenType::RightBrace, None)),
-//             ',' => Some(self.make_token(TokenType::Comma, None)),
-//             '.' => Some(self.make_token(TokenType::Dot, None)),
-//             '-' => Some(self.make_token(TokenType::Minus, None)),
-//             '+' => Some(self.make_token(TokenType::Plus, None)),
-//             ';' => Some(self.make_token(TokenType::Semicolon, None)),
-//             '*' => Some(self.make_token(TokenType::Star, None)),
-
-//             // Single-or-double-char tokens
-//             '!' => {
-//                 if self.match_char('=') {
-//                     Some(self.make_token(TokenType::BangEqual, None))
-//                 } else {
-//                     Some(self.make_token(TokenType::Bang, None))
-//                 }
-//             }
-//             '=' => {
-//                 if self.match_char('=') {
-//                     Some(self.make_token(TokenType::EqualEqual, None))
-//                 } else {
-//                     Some(self.make_token(TokenType::Equal, None))
-//                 }
-//             }
-//             '<' => {
-//                 if self.match_char('=') {
-//                     Some(self.make_token(TokenType::LesserEqual, None))
-//                 } else {
-//                     Some(self.make_token(TokenType::Lesser, None))
-//                 }
-//             }
-//             '>' => {
-//                 if self.match_char('=') {
-//                     Some(self.make_token(TokenType::GreaterEqual, None))
-//                 } else {
-//                     Some(self.make_token(TokenType::Greater, None))
-//                 }
-//             }
-
-//             // Could be single-char token, or a comment
-//             '/' => {
-//                 if self.match_char('/') {
-//                     while self.peek() != '\n' && !self.is_at_end() {
-//                         self.next();
-//                     }
-//                     None
-//                 } else {
-//                     Some(self.make_token(TokenType::Slash, None))
-//                 }
-//             }
-
-//             // Ignore Whitespace
-//             ' ' | '\r' | '\t' => None,
-
-//             '\n' => {
-//                 self.line += 1;
-//                 self.scan_tokens()?
-//             }
-
-//             '"' => Some(self.scan_string()?),
-
-//             digit if self.is_digit(digit) => Some(self.scan_number()?),
-
-//             alpha if self.is_alpha(alpha) => Some(self.scan_ident()?),
-
-//             _ => return Err(ErrorKind::LoxError(self.line, "no matched token".into()).into()),
-//         })
-//     }
-
-//     fn is_digit(&self, ch: char) -> bool {
-//         ch >= '0' && ch <= '9'
-//     }
-
-//     fn is_alpha(&self, ch: char) -> bool {
-//         (ch >= 'a' && ch <= 'z') || (ch >= 'A' && ch <= 'Z') || ch == '_'
-//     }
-
-//     fn is_alphanumeric(&self, ch: char) -> bool {
-//         self.is_alpha(ch) || self.is_digit(ch)
-//     }
-
-//     fn scan_ident(&mut self) -> Result<Token> {
-//         while self.is_alphanumeric(self.peek()) {
-//             self.next();
-//         }
-
-//         let text = self.characters[self.start..self.current]
-//             .iter()
-//             .cloned()
-//             .collect::<String>();
-//         if let Some(ttype) = TokenType::keyword(&text) {
-//             Ok(self.make_token(ttype, None))
-//         } else {
-//             Ok(self.make_token(TokenType::Identifier, None))
-//         }
-//     }
-
-//     fn scan_number(&mut self) -> Result<Token> {
-//         let mut is_float = false;
-//         while self.is_digit(self.peek()) {
-//             self.next();
-//         }
-
-//         if self.peek() == '.' && self.is_digit(self.peek_next()) {
-//             is_float = true;
-//             // Consume the '.'
-//             self.next();
-
-//             while self.is_digit(self.peek()) {
-//                 self.next();
-//             }
-//         }
-
-//         let lexeme = self.characters[self.start..self.current]
-//             .iter()
-//             .cloned()
-//             .collect::<String>();
-//         Ok(self.make_token(
-//             TokenType::Number,
-//             if is_float {
-//                 let parsed: f64 = lexeme.parse()?;
-//                 let literal = Number::Float(parsed);
-//                 Some(Literal::Number(literal))
-//             } else {
-//                 let parsed: i64 = lexeme.parse()?;
-//                 let literal = Number::Int(parsed);
-//                 Some(Literal::Number(literal))
-//             },
-//         ))
-//     }
-
-//     fn scan_string(&mut self) -> Result<Token> {
-//         while self.peek() != '"' && !self.is_at_end() {
-//             if self.peek() == '\n' {
-//                 self.line += 1;
-//             }
-//             self.next();
-//         }
-
-//         if self.is_at_end() {
-//             return Err(ErrorKind::LoxError(self.line, "unterminated string!".into()).into());
-//         }
-
-//         // the closing '"'
-//         self.next();
-
-//         let start = self.start + 1;
-//         let end = self.current - 1;
-//         let val = self.characters[start..end]
-//             .iter()
-//             .cloned()
-//             .collect::<String>();
-//         let lit = Literal::String(val);
-//         Ok(self.make_token(TokenType::String, Some(lit)))
-//     }
-
-//     fn peek(&self) -> char {
-//         if self.is_at_end() {
-//             '\0'
-//         } else {
-//             self.characters[self.current]
-//         }
-//     }
-
-//     fn peek_next(&self) -> char {
-//         if self.current + 1 >= self.characters.len() {
-//             '\0'
-//         } else {
-//             self.characters[self.current + 1]
-//         }
-//     }
-
-//     fn next(&mut self) -> char {
-//         self.current += 1;
-//         self.characters[self.current - 1]
-//     }
-
-//     fn match_char(&mut self, expected: char) -> bool {
-//         if self.is_at_end() {
-//             return false;
-//         }
-//         if self.characters[self.current] != expected {
-//             return false;
-//         }
-
-//         self.current += 1;
-//         return true;
-//     }
-
-//     fn make_token(&mut self, ttype: TokenType, literal: Option<Literal>) -> Token {
-//         let lexeme = self.characters[self.start..self.current]
-//             .iter()
-//             .cloned()
-//             .collect::<String>();
-//         Token::new(ttype, &lexeme, literal, self.line)
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use crate::errors::ErrorCode;
+    use crate::scanner::Scanner;
+    use crate::span::{Position, Span};
+    use crate::token::{Item, Token};
+
+    // Every token but the trailing EOF, with its line.
+    fn lines(source: &str) -> Vec<(Token, usize)> {
+        let mut items = Scanner::new(source).scan_tokens().expect("Failed scanning");
+        assert_eq!(Some(Token::Eof), items.pop().map(|item| item.token));
+        items
+            .into_iter()
+            .map(|item| {
+                let line = item.line();
+                (item.token, line)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn scan_newlines() {
+        assert_eq!(
+            vec![
+                (Token::Var, 1),
+                (Token::Identifier("a".to_string()), 1),
+                (Token::Equal, 1),
+                (Token::Number(1.0), 1),
+                (Token::SemiColon, 1),
+                (Token::Print, 3),
+                (Token::Identifier("a".to_string()), 3),
+                (Token::SemiColon, 3),
+            ],
+            lines("var a = 1;\n\nprint a;\n")
+        );
+        assert_eq!(vec![(Token::Nil, 2)], lines("\r\n  nil"));
+    }
+
+    #[test]
+    fn scan_comments_across_lines() {
+        assert_eq!(
+            vec![
+                (Token::Number(1.0), 2),
+                (Token::Slash, 2),
+                (Token::Number(2.0), 2),
+                (Token::Identifier("end".to_string()), 4),
+            ],
+            lines("// first line comment\n1 / 2 // trailing comment\n// another\nend")
+        );
+        assert_eq!(Vec::<(Token, usize)>::new(), lines("// only a comment"));
+    }
+
+    #[test]
+    fn scan_multiline_strings() {
+        assert_eq!(
+            vec![
+                (Token::Print, 1),
+                (Token::String("one\ntwo\nthree".to_string()), 1),
+                (Token::SemiColon, 3),
+                (Token::Identifier("after".to_string()), 4),
+            ],
+            lines("print \"one\ntwo\nthree\";\nafter")
+        );
+    }
+
+    #[test]
+    fn scan_multiline_program() {
+        let source = "fun add(a, b) {\n  // sum\n  return a + b;\n}\n\nprint add(1, 2);\n";
+        let expected_lines = vec![
+            1, 1, 1, 1, 1, 1, 1, 1, // fun add ( a , b ) {
+            3, 3, 3, 3, 3, // return a + b ;
+            4, // }
+            6, 6, 6, 6, 6, 6, 6, 6, // print add ( 1 , 2 ) ;
+        ];
+        let actual_lines = lines(source)
+            .into_iter()
+            .map(|(_, line)| line)
+            .collect::<Vec<_>>();
+        assert_eq!(expected_lines, actual_lines);
+    }
+
+    #[test]
+    fn scan_reports_every_error() {
+        let errors = Scanner::new("var a = 1;\n@ # print a;\nvar b = \"open")
+            .scan_tokens()
+            .unwrap_err();
+        let reported = errors
+            .iter()
+            .map(|error| (error.code, error.line(), error.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (
+                    ErrorCode::UnexpectedCharacter,
+                    2,
+                    "Unexpected character '@'."
+                ),
+                (
+                    ErrorCode::UnexpectedCharacter,
+                    2,
+                    "Unexpected character '#'."
+                ),
+                (ErrorCode::UnterminatedString, 3, "Unterminated string."),
+            ],
+            reported
+        );
+    }
+
+    #[test]
+    fn scan_end_of_input() {
+        assert_eq!(vec![(Token::Number(12.5), 1)], lines("12.5"));
+        assert_eq!(vec![(Token::Number(1.0), 1), (Token::Dot, 1)], lines("1."));
+        assert_eq!(vec![(Token::Bang, 1)], lines("!"));
+        assert_eq!(
+            vec![(Token::Identifier("abc".to_string()), 1)],
+            lines("abc")
+        );
+        assert_eq!(
+            vec![(Token::Number(99999999999999999999.0), 1)],
+            lines("99999999999999999999")
+        );
+    }
+
+    #[test]
+    fn scan_eof_and_columns() {
+        let items = Scanner::new("var a;\n  print \"x\ny\" a;\n")
+            .scan_tokens()
+            .expect("Failed scanning");
+        let positions = items
+            .iter()
+            .map(|item| (item.token.clone(), item.line(), item.span.start.column))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (Token::Var, 1, 1),
+                (Token::Identifier("a".to_string()), 1, 5),
+                (Token::SemiColon, 1, 6),
+                (Token::Print, 2, 3),
+                (Token::String("x\ny".to_string()), 2, 9),
+                (Token::Identifier("a".to_string()), 3, 4),
+                (Token::SemiColon, 3, 5),
+                (Token::Eof, 4, 1),
+            ],
+            positions
+        );
+
+        let items = Scanner::new("").scan_tokens().expect("Failed scanning");
+        assert_eq!(
+            vec![Item::new(String::new(), Token::Eof, Span::default())],
+            items
+        );
+
+        let items = Scanner::new("1 + 2")
+            .scan_tokens()
+            .expect("Failed scanning");
+        let eof = items.last().unwrap();
+        let end = Position::new(5, 1, 6);
+        assert_eq!((&Token::Eof, Span::new(end, end)), (&eof.token, eof.span));
+    }
+
+    #[test]
+    fn scan_spans() {
+        let items = Scanner::new("var s = \"é\nb\";\n12.5")
+            .scan_tokens()
+            .expect("Failed scanning");
+        let spans = items
+            .iter()
+            .map(|item| {
+                let (start, end) = (item.span.start, item.span.end);
+                (
+                    (start.offset, start.line, start.column),
+                    (end.offset, end.line, end.column),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ((0, 1, 1), (3, 1, 4)),   // var
+                ((4, 1, 5), (5, 1, 6)),   // s
+                ((6, 1, 7), (7, 1, 8)),   // =
+                ((8, 1, 9), (14, 2, 3)),  // "é\nb", where é is two bytes wide
+                ((14, 2, 3), (15, 2, 4)), // ;
+                ((16, 3, 1), (20, 3, 5)), // 12.5
+                ((20, 3, 5), (20, 3, 5)), // EOF
+            ],
+            spans
+        );
+    }
+}
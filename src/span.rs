@@ -0,0 +1,167 @@
+use std::fmt;
+
+/// A point in the source. `offset` is in bytes, `line` and `column` are 1-based and count
+/// characters, so they match what an editor shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub fn new(offset: usize, line: usize, column: usize) -> Position {
+        Position {
+            offset,
+            line,
+            column,
+        }
+    }
+}
+
+impl Default for Position {
+    fn default() -> Position {
+        Position::new(0, 1, 1)
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A half-open range of source, from `start` up to but excluding `end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Span {
+        Span { start, end }
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(&self, other: &Span) -> Span {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end.offset - self.start.offset
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+/// Maps byte offsets of one source text back to lines and columns.
+pub struct SourceMap {
+    source: String,
+    // Byte offset at which each line starts; the first line always starts at 0.
+    line_starts: Vec<usize>,
+}
+
+impl SourceMap {
+    pub fn new(source: &str) -> SourceMap {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        SourceMap {
+            source: source.to_string(),
+            line_starts,
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Offsets past the end are clamped to the end of the source, and offsets inside a
+    /// multi-byte character resolve to that character.
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.source.len());
+        let line_index = match self.line_starts.binary_search(&offset) {
+            Ok(index) => index,
+            Err(index) => index - 1,
+        };
+        let line_start = self.line_starts[line_index];
+        let column = self.source[line_start..]
+            .char_indices()
+            .take_while(|(index, _)| line_start + index < offset)
+            .count()
+            + 1;
+        Position::new(offset, line_index + 1, column)
+    }
+
+    pub fn span(&self, start: usize, end: usize) -> Span {
+        Span::new(self.position(start), self.position(end))
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// The text of a 1-based line, without its line terminator.
+    pub fn line(&self, line: usize) -> Option<&str> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.source.len(), |next| next - 1);
+        Some(self.source[start..end].trim_end_matches('\r'))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::span::{Position, SourceMap, Span};
+
+    #[test]
+    fn source_map_positions() {
+        let map = SourceMap::new("ab\n\ncé d\n");
+        assert_eq!(Position::new(0, 1, 1), map.position(0));
+        assert_eq!(Position::new(2, 1, 3), map.position(2));
+        assert_eq!(Position::new(3, 2, 1), map.position(3));
+        assert_eq!(Position::new(4, 3, 1), map.position(4));
+        // 'é' takes two bytes but a single column.
+        assert_eq!(Position::new(7, 3, 3), map.position(7));
+        assert_eq!(Position::new(8, 3, 4), map.position(8));
+        assert_eq!(Position::new(10, 4, 1), map.position(10));
+        assert_eq!(Position::new(10, 4, 1), map.position(99));
+    }
+
+    #[test]
+    fn source_map_lines() {
+        let map = SourceMap::new("first\r\nsecond\n\nlast");
+        assert_eq!(4, map.line_count());
+        assert_eq!(Some("first"), map.line(1));
+        assert_eq!(Some("second"), map.line(2));
+        assert_eq!(Some(""), map.line(3));
+        assert_eq!(Some("last"), map.line(4));
+        assert_eq!(None, map.line(0));
+        assert_eq!(None, map.line(5));
+    }
+
+    #[test]
+    fn span_union() {
+        let map = SourceMap::new("1 + 2\n  * 3");
+        let left = map.span(0, 1);
+        let right = map.span(10, 11);
+        let union = Span::new(Position::new(0, 1, 1), Position::new(11, 2, 6));
+        assert_eq!(union, left.to(&right));
+        assert_eq!(union, right.to(&left));
+        assert_eq!(11, union.len());
+    }
+}
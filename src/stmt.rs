@@ -0,0 +1,182 @@
+use crate::expr::{Expr, VisitorPrinter};
+use crate::token::Item;
+use std::rc::Rc;
+
+#[derive(Debug, PartialEq)]
+pub struct FunctionDecl {
+    pub name: Item,
+    pub params: Vec<Item>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Stmt {
+    Expression(Expr),
+    Print(Expr),
+    Var(Item, Option<Expr>),
+    Block(Vec<Stmt>),
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    While(Expr, Box<Stmt>),
+    Function(Rc<FunctionDecl>),
+    Return(Item, Option<Expr>),
+    Class(Item, Option<Expr>, Vec<Rc<FunctionDecl>>),
+}
+
+pub trait Visitor<T> {
+    fn visit_stmt(&mut self, stmt: &Stmt) -> T {
+        use Stmt::*;
+
+        match stmt {
+            Expression(expression) => self.visit_stmt_expression(expression),
+            Print(expression) => self.visit_stmt_print(expression),
+            Var(name, initializer) => self.visit_stmt_var(name, initializer.as_ref()),
+            Block(statements) => self.visit_stmt_block(statements),
+            If(condition, then_branch, else_branch) => {
+                self.visit_stmt_if(condition, then_branch, else_branch.as_deref())
+            }
+            While(condition, body) => self.visit_stmt_while(condition, body),
+            Function(declaration) => self.visit_stmt_function(declaration),
+            Return(keyword, value) => self.visit_stmt_return(keyword, value.as_ref()),
+            Class(name, superclass, methods) => {
+                self.visit_stmt_class(name, superclass.as_ref(), methods)
+            }
+        }
+    }
+
+    fn visit_stmt_expression(&mut self, expr: &Expr) -> T;
+
+    fn visit_stmt_print(&mut self, expr: &Expr) -> T;
+
+    fn visit_stmt_var(&mut self, name: &Item, initializer: Option<&Expr>) -> T;
+
+    fn visit_stmt_block(&mut self, statements: &[Stmt]) -> T;
+
+    fn visit_stmt_if(
+        &mut self,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: Option<&Stmt>,
+    ) -> T;
+
+    fn visit_stmt_while(&mut self, condition: &Expr, body: &Stmt) -> T;
+
+    fn visit_stmt_function(&mut self, declaration: &Rc<FunctionDecl>) -> T;
+
+    fn visit_stmt_return(&mut self, keyword: &Item, value: Option<&Expr>) -> T;
+
+    fn visit_stmt_class(
+        &mut self,
+        name: &Item,
+        superclass: Option<&Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) -> T;
+}
+
+impl VisitorPrinter {
+    pub fn print_stmt(&mut self, stmt: &Stmt) -> String {
+        self.visit_stmt(stmt)
+    }
+
+    fn print_function(&mut self, keyword: &str, declaration: &FunctionDecl) -> String {
+        let params = declaration
+            .params
+            .iter()
+            .map(|param| param.lexeme.clone())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut output = format!("{} {} ({})", keyword, declaration.name.lexeme, params);
+        for statement in declaration.body.iter() {
+            output.push(' ');
+            output.push_str(&self.visit_stmt(statement));
+        }
+        self.parenthesize(output)
+    }
+}
+
+impl Visitor<String> for VisitorPrinter {
+    fn visit_stmt_expression(&mut self, expr: &Expr) -> String {
+        let printed_expr = self.print(expr);
+        self.parenthesize(format!("; {}", printed_expr))
+    }
+
+    fn visit_stmt_print(&mut self, expr: &Expr) -> String {
+        let printed_expr = self.print(expr);
+        self.parenthesize(format!("print {}", printed_expr))
+    }
+
+    fn visit_stmt_var(&mut self, name: &Item, initializer: Option<&Expr>) -> String {
+        match initializer {
+            Some(initializer) => {
+                let printed_initializer = self.print(initializer);
+                self.parenthesize(format!("var {} = {}", name.lexeme, printed_initializer))
+            }
+            None => self.parenthesize(format!("var {}", name.lexeme)),
+        }
+    }
+
+    fn visit_stmt_block(&mut self, statements: &[Stmt]) -> String {
+        let mut output = String::from("block");
+        for statement in statements {
+            output.push(' ');
+            output.push_str(&self.visit_stmt(statement));
+        }
+        self.parenthesize(output)
+    }
+
+    fn visit_stmt_if(
+        &mut self,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: Option<&Stmt>,
+    ) -> String {
+        let printed_condition = self.print(condition);
+        let printed_then = self.visit_stmt(then_branch);
+        match else_branch {
+            Some(else_branch) => {
+                let printed_else = self.visit_stmt(else_branch);
+                self.parenthesize(format!(
+                    "if {} {} {}",
+                    printed_condition, printed_then, printed_else
+                ))
+            }
+            None => self.parenthesize(format!("if {} {}", printed_condition, printed_then)),
+        }
+    }
+
+    fn visit_stmt_while(&mut self, condition: &Expr, body: &Stmt) -> String {
+        let printed_condition = self.print(condition);
+        let printed_body = self.visit_stmt(body);
+        self.parenthesize(format!("while {} {}", printed_condition, printed_body))
+    }
+
+    fn visit_stmt_function(&mut self, declaration: &Rc<FunctionDecl>) -> String {
+        self.print_function("fun", declaration)
+    }
+
+    fn visit_stmt_return(&mut self, _keyword: &Item, value: Option<&Expr>) -> String {
+        match value {
+            Some(value) => {
+                let printed_value = self.print(value);
+                self.parenthesize(format!("return {}", printed_value))
+            }
+            None => self.parenthesize("return".to_string()),
+        }
+    }
+
+    fn visit_stmt_class(
+        &mut self,
+        name: &Item,
+        superclass: Option<&Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) -> String {
+        let mut output = format!("class {}", name.lexeme);
+        if let Some(superclass) = superclass {
+            output.push_str(&format!(" < {}", self.print(superclass)));
+        }
+        for method in methods {
+            output.push(' ');
+            output.push_str(&self.print_function("method", method));
+        }
+        self.parenthesize(output)
+    }
+}
@@ -1,6 +1,7 @@
+use crate::span::{Position, Span};
+use std::fmt;
 
-
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     // Single-character tokens.
     LeftParen,
@@ -51,42 +52,224 @@ pub enum Token {
     Eof,
 }
 
-impl ToString for Token {
-    fn to_string(&self) -> String {
+impl Token {
+    /// The reserved words of the language.
+    pub const KEYWORDS: [Token; 16] = [
+        Token::And,
+        Token::Class,
+        Token::Else,
+        Token::False,
+        Token::Fun,
+        Token::For,
+        Token::If,
+        Token::Nil,
+        Token::Or,
+        Token::Print,
+        Token::Return,
+        Token::Super,
+        Token::This,
+        Token::True,
+        Token::Var,
+        Token::While,
+    ];
+
+    /// The keyword spelled `text`, if it is one.
+    pub fn keyword(text: &str) -> Option<Token> {
+        Token::KEYWORDS
+            .iter()
+            .find(|keyword| keyword.to_string() == text)
+            .cloned()
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use Token::*;
         match self {
-            Number(value)  => value.to_string(),
-            EqualEqual => "==".to_string(),
-            Equal => "=".to_string(),
-            Star => "*".to_string(),
-            Minus => "-".to_string(),
-            Plus => "+".to_string(),
-
-            _ => "Any".to_string()
+            LeftParen => write!(f, "("),
+            RightParen => write!(f, ")"),
+            LeftBrace => write!(f, "{{"),
+            RightBrace => write!(f, "}}"),
+            Comma => write!(f, ","),
+            Dot => write!(f, "."),
+            Minus => write!(f, "-"),
+            Plus => write!(f, "+"),
+            SemiColon => write!(f, ";"),
+            Slash => write!(f, "/"),
+            Star => write!(f, "*"),
+
+            Bang => write!(f, "!"),
+            BangEqual => write!(f, "!="),
+            Equal => write!(f, "="),
+            EqualEqual => write!(f, "=="),
+            Greater => write!(f, ">"),
+            GreaterEqual => write!(f, ">="),
+            Lesser => write!(f, "<"),
+            LesserEqual => write!(f, "<="),
+
+            Identifier(name) => write!(f, "{}", name),
+            String(value) => write!(f, "{}", value),
+            Number(value) => write!(f, "{}", value),
+
+            And => write!(f, "and"),
+            Class => write!(f, "class"),
+            Else => write!(f, "else"),
+            False => write!(f, "false"),
+            Fun => write!(f, "fun"),
+            For => write!(f, "for"),
+            If => write!(f, "if"),
+            Nil => write!(f, "nil"),
+            Or => write!(f, "or"),
+            Print => write!(f, "print"),
+            Return => write!(f, "return"),
+            Super => write!(f, "super"),
+            This => write!(f, "this"),
+            True => write!(f, "true"),
+            Var => write!(f, "var"),
+            While => write!(f, "while"),
+
+            Eof => write!(f, "end of file"),
         }
     }
 }
 
-
-
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Item {
     pub token: Token,
     pub lexeme: String,
-    pub line: usize,
+    pub span: Span,
 }
 
 impl Item {
-
-    pub fn new(lexeme: String, token: Token, line: usize ) -> Item {
+    pub fn new(lexeme: String, token: Token, span: Span) -> Item {
         Item {
             lexeme,
             token,
-            line
+            span,
+        }
+    }
+
+    /// The line the item starts on.
+    pub fn line(&self) -> usize {
+        self.span.start.line
+    }
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} {} {}", self.token, self.lexeme, self.span)
+    }
+}
+
+/// Cursor over scanned items, always terminated by a `Token::Eof` item so that a parser never
+/// has to special-case running off the end.
+pub struct TokenStream {
+    items: Vec<Item>,
+    current: usize,
+}
+
+impl TokenStream {
+    pub fn new(mut items: Vec<Item>) -> TokenStream {
+        if items.last().map(|item| &item.token) != Some(&Token::Eof) {
+            let end = items
+                .last()
+                .map_or(Position::default(), |item| item.span.end);
+            items.push(Item::new(String::new(), Token::Eof, Span::new(end, end)));
+        }
+        TokenStream { items, current: 0 }
+    }
+
+    /// The item about to be consumed; `Token::Eof` once everything else has been.
+    pub fn peek(&self) -> &Item {
+        &self.items[self.current]
+    }
+
+    /// The item right after `peek()`, sticking to `Token::Eof` at the end.
+    pub fn peek_next(&self) -> &Item {
+        let next = (self.current + 1).min(self.items.len() - 1);
+        &self.items[next]
+    }
+
+    /// The most recently consumed item.
+    pub fn previous(&self) -> &Item {
+        &self.items[self.current.saturating_sub(1)]
+    }
+
+    pub fn is_at_end(&self) -> bool {
+        self.peek().token == Token::Eof
+    }
+
+    /// Consumes the current item and returns it. At the end, the EOF item is returned and the
+    /// cursor stays put.
+    pub fn advance(&mut self) -> &Item {
+        if self.is_at_end() {
+            return self.peek();
         }
+        self.current += 1;
+        self.previous()
+    }
+
+    pub fn check(&self, token: &Token) -> bool {
+        !self.is_at_end() && &self.peek().token == token
     }
 
-    pub fn to_string(self) -> String {
-        format!("{:?} {} {}", self.token, self.lexeme, self.line)
+    /// Consumes the current item if it is any of `tokens`.
+    pub fn match_any(&mut self, tokens: &[Token]) -> bool {
+        if tokens.iter().any(|token| self.check(token)) {
+            self.advance();
+            return true;
+        }
+        false
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::span::{Position, Span};
+    use crate::token::{Item, Token, TokenStream};
+
+    fn item(token: Token, column: usize) -> Item {
+        let length = token.to_string().len();
+        let start = Position::new(column - 1, 1, column);
+        let end = Position::new(column - 1 + length, 1, column + length);
+        Item::new(token.to_string(), token, Span::new(start, end))
+    }
+
+    #[test]
+    fn stream_cursor() {
+        let mut stream = TokenStream::new(vec![
+            item(Token::Print, 1),
+            item(Token::Nil, 7),
+            item(Token::SemiColon, 10),
+        ]);
+
+        assert_eq!(Token::Print, stream.peek().token);
+        assert_eq!(Token::Nil, stream.peek_next().token);
+        assert!(stream.check(&Token::Print));
+        assert!(!stream.match_any(&[Token::Nil, Token::SemiColon]));
+        assert!(stream.match_any(&[Token::Nil, Token::Print]));
+        assert_eq!(Token::Print, stream.previous().token);
+
+        assert_eq!(Token::Nil, stream.advance().token);
+        assert_eq!(Token::SemiColon, stream.peek().token);
+        assert_eq!(Token::Eof, stream.peek_next().token);
+        assert_eq!(Token::SemiColon, stream.advance().token);
+
+        assert!(stream.is_at_end());
+        assert!(!stream.check(&Token::Eof));
+        assert_eq!(Token::Eof, stream.advance().token);
+        assert_eq!(Token::Eof, stream.peek_next().token);
+    }
+
+    #[test]
+    fn stream_appends_missing_eof() {
+        let stream = TokenStream::new(vec![item(Token::Nil, 3)]);
+        assert_eq!(Token::Eof, stream.peek_next().token);
+        let end = Position::new(5, 1, 6);
+        assert_eq!(Span::new(end, end), stream.peek_next().span);
+
+        let stream = TokenStream::new(vec![]);
+        assert!(stream.is_at_end());
+        assert_eq!(Span::default(), stream.peek().span);
+    }
+}
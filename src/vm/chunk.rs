@@ -0,0 +1,172 @@
+use crate::span::Span;
+use crate::vm::object::Value;
+
+/// The instructions of the virtual machine. Operands follow the opcode byte: a one-byte index
+/// into the constant pool, the stack or the closure's upvalues for most, a two-byte big-endian
+/// distance for jumps. `Closure` is followed by a pair of bytes per variable the function
+/// captures: whether it is a local of the enclosing function, and its slot or upvalue index there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum OpCode {
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    GetLocal,
+    SetLocal,
+    GetUpvalue,
+    SetUpvalue,
+    GetGlobal,
+    DefineGlobal,
+    SetGlobal,
+    GetProperty,
+    SetProperty,
+    GetSuper,
+    Equal,
+    Greater,
+    Less,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    Jump,
+    JumpIfFalse,
+    Loop,
+    Call,
+    Closure,
+    CloseUpvalue,
+    Return,
+    Class,
+    Inherit,
+    Method,
+}
+
+impl OpCode {
+    const ALL: [OpCode; 35] = [
+        OpCode::Constant,
+        OpCode::Nil,
+        OpCode::True,
+        OpCode::False,
+        OpCode::Pop,
+        OpCode::GetLocal,
+        OpCode::SetLocal,
+        OpCode::GetUpvalue,
+        OpCode::SetUpvalue,
+        OpCode::GetGlobal,
+        OpCode::DefineGlobal,
+        OpCode::SetGlobal,
+        OpCode::GetProperty,
+        OpCode::SetProperty,
+        OpCode::GetSuper,
+        OpCode::Equal,
+        OpCode::Greater,
+        OpCode::Less,
+        OpCode::Add,
+        OpCode::Subtract,
+        OpCode::Multiply,
+        OpCode::Divide,
+        OpCode::Not,
+        OpCode::Negate,
+        OpCode::Print,
+        OpCode::Jump,
+        OpCode::JumpIfFalse,
+        OpCode::Loop,
+        OpCode::Call,
+        OpCode::Closure,
+        OpCode::CloseUpvalue,
+        OpCode::Return,
+        OpCode::Class,
+        OpCode::Inherit,
+        OpCode::Method,
+    ];
+
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        OpCode::ALL.get(byte as usize).copied()
+    }
+}
+
+/// A function's bytecode with its constant pool and a table mapping instructions back to the
+/// source they were compiled from.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Value>,
+    // Run-length encoded: the offset where each run of bytes sharing a span starts.
+    spans: Vec<(usize, Span)>,
+}
+
+impl Chunk {
+    pub fn write(&mut self, byte: u8, span: Span) {
+        if self.spans.last().map(|(_, last)| *last) != Some(span) {
+            self.spans.push((self.code.len(), span));
+        }
+        self.code.push(byte);
+    }
+
+    pub fn write_op(&mut self, op: OpCode, span: Span) {
+        self.write(op as u8, span);
+    }
+
+    /// Adds `value` to the constant pool, reusing an equal entry, and returns its index.
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        match self
+            .constants
+            .iter()
+            .position(|constant| *constant == value)
+        {
+            Some(index) => index,
+            None => {
+                self.constants.push(value);
+                self.constants.len() - 1
+            }
+        }
+    }
+
+    /// The source of the instruction the byte at `offset` belongs to.
+    pub fn span(&self, offset: usize) -> Span {
+        let run = self.spans.partition_point(|(start, _)| *start <= offset);
+        self.spans
+            .get(run.wrapping_sub(1))
+            .map_or(Span::default(), |(_, span)| *span)
+    }
+
+    pub fn line(&self, offset: usize) -> usize {
+        self.span(offset).start.line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::span::SourceMap;
+    use crate::vm::chunk::{Chunk, OpCode};
+    use crate::vm::object::Value;
+
+    #[test]
+    fn chunk_spans_and_constants() {
+        let map = SourceMap::new("1;\n-2;");
+        let mut chunk = Chunk::default();
+        let one = chunk.add_constant(Value::Number(1.0));
+        chunk.write_op(OpCode::Constant, map.span(0, 1));
+        chunk.write(one as u8, map.span(0, 1));
+        chunk.write_op(OpCode::Pop, map.span(1, 2));
+        let two = chunk.add_constant(Value::Number(2.0));
+        chunk.write_op(OpCode::Constant, map.span(4, 5));
+        chunk.write(two as u8, map.span(4, 5));
+        chunk.write_op(OpCode::Negate, map.span(3, 4));
+
+        assert_eq!(0, chunk.add_constant(Value::Number(1.0)));
+        assert_eq!(2, chunk.constants.len());
+        assert_eq!(map.span(0, 1), chunk.span(1));
+        assert_eq!(map.span(1, 2), chunk.span(2));
+        assert_eq!(
+            vec![1, 1, 1, 2, 2, 2],
+            (0..6).map(|offset| chunk.line(offset)).collect::<Vec<_>>()
+        );
+        assert_eq!(Some(OpCode::Negate), OpCode::from_byte(chunk.code[5]));
+        assert_eq!(None, OpCode::from_byte(255));
+    }
+}
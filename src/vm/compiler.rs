@@ -0,0 +1,676 @@
+use crate::errors::{ErrorCode, LoxError};
+use crate::expr::{self, Expr, ExprId};
+use crate::span::Span;
+use crate::stmt::{self, FunctionDecl, Stmt};
+use crate::token::{Item, Token};
+use crate::vm::chunk::{Chunk, OpCode};
+use crate::vm::heap::Heap;
+use crate::vm::object::{Function, ObjRef, Object, Value};
+use std::rc::Rc;
+
+// Operands are single bytes, and slot 0 of every frame holds the callee or `this`.
+const MAX_CONSTANTS: usize = 256;
+const MAX_LOCALS: usize = 256;
+const MAX_UPVALUES: usize = 256;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+struct Local {
+    name: String,
+    depth: usize,
+    // Set once a closure captures it, so that it is closed rather than popped.
+    is_captured: bool,
+}
+
+/// Where a closure finds a variable it captures when it is created: in a local slot of the
+/// function declaring it, or among that function's own upvalues.
+#[derive(Clone, Copy, PartialEq)]
+struct Capture {
+    index: u8,
+    is_local: bool,
+}
+
+// A function whose body is being compiled, enclosing ones further down the stack.
+struct FunctionState {
+    function: Function,
+    kind: FunctionKind,
+    locals: Vec<Local>,
+    upvalues: Vec<Capture>,
+    scope_depth: usize,
+}
+
+impl FunctionState {
+    fn new(name: &str, kind: FunctionKind) -> FunctionState {
+        // Methods find their receiver in slot 0, other functions leave it unnamed.
+        let receiver = match kind {
+            FunctionKind::Method | FunctionKind::Initializer => "this",
+            FunctionKind::Script | FunctionKind::Function => "",
+        };
+        FunctionState {
+            function: Function {
+                name: name.to_string(),
+                ..Function::default()
+            },
+            kind,
+            locals: vec![Local {
+                name: receiver.to_string(),
+                depth: 0,
+                is_captured: false,
+            }],
+            upvalues: vec![],
+            scope_depth: 0,
+        }
+    }
+
+    fn resolve_local(&self, name: &str) -> Option<u8> {
+        self.locals
+            .iter()
+            .rposition(|local| local.name == name)
+            .map(|slot| slot as u8)
+    }
+}
+
+/// Turns a resolved program into bytecode for the virtual machine, one function object per
+/// function declaration plus one for the top-level script.
+pub struct Compiler {
+    heap: Heap,
+    functions: Vec<FunctionState>,
+    errors: Vec<LoxError>,
+}
+
+impl Compiler {
+    /// Compiles `statements` into a script function allocated on `heap`.
+    pub fn compile(
+        mut self,
+        statements: &[Stmt],
+        heap: &mut Heap,
+    ) -> Result<ObjRef, Vec<LoxError>> {
+        std::mem::swap(&mut self.heap, heap);
+        self.functions
+            .push(FunctionState::new("", FunctionKind::Script));
+        // The script returns the value of its final expression statement, like a REPL shows it.
+        match statements.split_last() {
+            Some((Stmt::Expression(expr), rest)) => {
+                for statement in rest {
+                    self.compile_stmt(statement);
+                }
+                self.compile_expr(expr);
+                self.emit(OpCode::Return, expr.span);
+            }
+            _ => {
+                for statement in statements {
+                    self.compile_stmt(statement);
+                }
+            }
+        }
+        let span = self.last_span();
+        let (function, _) = self.end_function(span);
+        let script = self.heap.alloc(Object::Function(function));
+        std::mem::swap(&mut self.heap, heap);
+
+        if self.errors.is_empty() {
+            Ok(script)
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn compile_stmt(&mut self, stmt: &Stmt) {
+        stmt::Visitor::visit_stmt(self, stmt)
+    }
+
+    fn compile_expr(&mut self, expr: &Expr) {
+        expr::Visitor::visit_expr(self, expr)
+    }
+
+    fn current(&mut self) -> &mut FunctionState {
+        self.functions
+            .last_mut()
+            .expect("No function being compiled")
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.current().function.chunk
+    }
+
+    // Where code that has no source of its own, like the pops closing a scope, is attributed.
+    fn last_span(&mut self) -> Span {
+        let chunk = self.chunk();
+        chunk.span(chunk.code.len().saturating_sub(1))
+    }
+
+    fn error(&mut self, span: Span, code: ErrorCode, message: &str) {
+        self.errors.push(LoxError::new(code, message, span));
+    }
+
+    fn emit(&mut self, op: OpCode, span: Span) {
+        self.chunk().write_op(op, span);
+    }
+
+    fn emit_with(&mut self, op: OpCode, operand: u8, span: Span) {
+        self.chunk().write_op(op, span);
+        self.chunk().write(operand, span);
+    }
+
+    fn make_constant(&mut self, value: Value, span: Span) -> u8 {
+        let index = self.chunk().add_constant(value);
+        if index >= MAX_CONSTANTS {
+            self.error(
+                span,
+                ErrorCode::TooManyConstants,
+                "Too many constants in one chunk.",
+            );
+            return 0;
+        }
+        index as u8
+    }
+
+    fn emit_constant(&mut self, value: Value, span: Span) {
+        let index = self.make_constant(value, span);
+        self.emit_with(OpCode::Constant, index, span);
+    }
+
+    fn identifier_constant(&mut self, name: &Item) -> u8 {
+        let string = self.heap.intern(&name.lexeme);
+        self.make_constant(Value::Object(string), name.span)
+    }
+
+    // Emits a jump with a placeholder distance and returns the offset to patch it at.
+    fn emit_jump(&mut self, op: OpCode, span: Span) -> usize {
+        self.emit(op, span);
+        self.chunk().write(0xff, span);
+        self.chunk().write(0xff, span);
+        self.chunk().code.len() - 2
+    }
+
+    fn patch_jump(&mut self, offset: usize, span: Span) {
+        let distance = self.chunk().code.len() - offset - 2;
+        if distance > u16::MAX as usize {
+            self.error(span, ErrorCode::JumpTooLarge, "Too much code to jump over.");
+        }
+        let code = &mut self.chunk().code;
+        code[offset] = (distance >> 8) as u8;
+        code[offset + 1] = distance as u8;
+    }
+
+    fn emit_loop(&mut self, start: usize, span: Span) {
+        self.emit(OpCode::Loop, span);
+        let distance = self.chunk().code.len() - start + 2;
+        if distance > u16::MAX as usize {
+            self.error(span, ErrorCode::JumpTooLarge, "Loop body too large.");
+        }
+        self.chunk().write((distance >> 8) as u8, span);
+        self.chunk().write(distance as u8, span);
+    }
+
+    fn emit_return(&mut self, span: Span) {
+        if self.current().kind == FunctionKind::Initializer {
+            self.emit_with(OpCode::GetLocal, 0, span);
+        } else {
+            self.emit(OpCode::Nil, span);
+        }
+        self.emit(OpCode::Return, span);
+    }
+
+    fn end_function(&mut self, span: Span) -> (Function, Vec<Capture>) {
+        self.emit_return(span);
+        let mut state = self.functions.pop().expect("No function being compiled");
+        state.function.upvalue_count = state.upvalues.len();
+        (state.function, state.upvalues)
+    }
+
+    fn begin_scope(&mut self) {
+        self.current().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        let span = self.last_span();
+        let state = self.current();
+        state.scope_depth -= 1;
+        let depth = state.scope_depth;
+        let mut ops = vec![];
+        while state.locals.last().is_some_and(|local| local.depth > depth) {
+            let local = state.locals.pop().expect("No local");
+            ops.push(if local.is_captured {
+                OpCode::CloseUpvalue
+            } else {
+                OpCode::Pop
+            });
+        }
+        for op in ops {
+            self.emit(op, span);
+        }
+    }
+
+    // Locals live on the stack in declaration order, so the slot is the value just pushed.
+    fn add_local(&mut self, name: &Item) {
+        if self.current().locals.len() >= MAX_LOCALS {
+            self.error(
+                name.span,
+                ErrorCode::TooManyLocals,
+                "Too many local variables in function.",
+            );
+            return;
+        }
+        let state = self.current();
+        let depth = state.scope_depth;
+        state.locals.push(Local {
+            name: name.lexeme.clone(),
+            depth,
+            is_captured: false,
+        });
+    }
+
+    // Declares `name` as a local when inside a scope, or returns the constant naming the global.
+    fn declare_variable(&mut self, name: &Item) -> Option<u8> {
+        if self.current().scope_depth > 0 {
+            self.add_local(name);
+            None
+        } else {
+            Some(self.identifier_constant(name))
+        }
+    }
+
+    fn define_variable(&mut self, global: Option<u8>, span: Span) {
+        if let Some(global) = global {
+            self.emit_with(OpCode::DefineGlobal, global, span);
+        }
+    }
+
+    // Finds `name` in the functions enclosing the one at `depth`, capturing it in each function
+    // in between so that the closure created at every level can hand it further in.
+    fn resolve_upvalue(&mut self, depth: usize, name: &Item) -> Option<u8> {
+        let enclosing = depth.checked_sub(1)?;
+        if let Some(slot) = self.functions[enclosing].resolve_local(&name.lexeme) {
+            self.functions[enclosing].locals[slot as usize].is_captured = true;
+            return Some(self.add_upvalue(depth, slot, true, name));
+        }
+        let index = self.resolve_upvalue(enclosing, name)?;
+        Some(self.add_upvalue(depth, index, false, name))
+    }
+
+    fn add_upvalue(&mut self, depth: usize, index: u8, is_local: bool, name: &Item) -> u8 {
+        let capture = Capture { index, is_local };
+        let upvalues = &mut self.functions[depth].upvalues;
+        if let Some(existing) = upvalues.iter().position(|upvalue| *upvalue == capture) {
+            return existing as u8;
+        }
+        if upvalues.len() >= MAX_UPVALUES {
+            self.error(
+                name.span,
+                ErrorCode::TooManyUpvalues,
+                "Too many closure variables in function.",
+            );
+            return 0;
+        }
+        upvalues.push(capture);
+        (upvalues.len() - 1) as u8
+    }
+
+    // Reads or writes `name`: a local slot when it is in scope, an upvalue when an enclosing
+    // function declared it, otherwise a global.
+    fn named_variable(&mut self, name: &Item, assign: bool) {
+        let depth = self.functions.len() - 1;
+        let (get, set, operand) = match self.functions[depth].resolve_local(&name.lexeme) {
+            Some(slot) => (OpCode::GetLocal, OpCode::SetLocal, slot),
+            None => match self.resolve_upvalue(depth, name) {
+                Some(index) => (OpCode::GetUpvalue, OpCode::SetUpvalue, index),
+                None => {
+                    let global = self.identifier_constant(name);
+                    (OpCode::GetGlobal, OpCode::SetGlobal, global)
+                }
+            },
+        };
+        self.emit_with(if assign { set } else { get }, operand, name.span);
+    }
+
+    fn function(&mut self, declaration: &FunctionDecl, kind: FunctionKind) {
+        self.functions
+            .push(FunctionState::new(&declaration.name.lexeme, kind));
+        self.begin_scope();
+        for param in &declaration.params {
+            self.add_local(param);
+        }
+        self.current().function.arity = declaration.params.len();
+        for statement in &declaration.body {
+            self.compile_stmt(statement);
+        }
+        let span = declaration.name.span;
+        let (function, upvalues) = self.end_function(span);
+
+        let function = self.heap.alloc(Object::Function(function));
+        let constant = self.make_constant(Value::Object(function), span);
+        self.emit_with(OpCode::Closure, constant, span);
+        for upvalue in upvalues {
+            self.chunk().write(upvalue.is_local as u8, span);
+            self.chunk().write(upvalue.index, span);
+        }
+    }
+}
+
+impl expr::Visitor<()> for Compiler {
+    fn new() -> Self {
+        Compiler {
+            heap: Heap::default(),
+            functions: vec![],
+            errors: vec![],
+        }
+    }
+
+    fn visit_expr_unary(&mut self, operator: &Item, expr: &Expr) {
+        self.compile_expr(expr);
+        match operator.token {
+            Token::Minus => self.emit(OpCode::Negate, operator.span),
+            _ => self.emit(OpCode::Not, operator.span),
+        }
+    }
+
+    fn visit_expr_binary(&mut self, left: &Expr, operator: &Item, right: &Expr) {
+        self.compile_expr(left);
+        self.compile_expr(right);
+        let span = operator.span;
+        match operator.token {
+            Token::Plus => self.emit(OpCode::Add, span),
+            Token::Minus => self.emit(OpCode::Subtract, span),
+            Token::Star => self.emit(OpCode::Multiply, span),
+            Token::Slash => self.emit(OpCode::Divide, span),
+            Token::Greater => self.emit(OpCode::Greater, span),
+            Token::Lesser => self.emit(OpCode::Less, span),
+            Token::EqualEqual => self.emit(OpCode::Equal, span),
+            Token::GreaterEqual => {
+                self.emit(OpCode::Less, span);
+                self.emit(OpCode::Not, span);
+            }
+            Token::LesserEqual => {
+                self.emit(OpCode::Greater, span);
+                self.emit(OpCode::Not, span);
+            }
+            _ => {
+                self.emit(OpCode::Equal, span);
+                self.emit(OpCode::Not, span);
+            }
+        }
+    }
+
+    fn visit_expr_grouping(&mut self, expr: &Expr) {
+        self.compile_expr(expr);
+    }
+
+    fn visit_expr_literal(&mut self, value: &Item) {
+        match &value.token {
+            Token::Nil => self.emit(OpCode::Nil, value.span),
+            Token::True => self.emit(OpCode::True, value.span),
+            Token::False => self.emit(OpCode::False, value.span),
+            Token::Number(number) => self.emit_constant(Value::Number(*number), value.span),
+            Token::String(string) => {
+                let string = self.heap.intern(string);
+                self.emit_constant(Value::Object(string), value.span);
+            }
+            _ => self.error(value.span, ErrorCode::Internal, "Unknown literal."),
+        }
+    }
+
+    // The left operand stays on the stack as the result when it decides the outcome.
+    fn visit_expr_logical(&mut self, left: &Expr, operator: &Item, right: &Expr) {
+        self.compile_expr(left);
+        let span = operator.span;
+        let end = if operator.token == Token::Or {
+            let right_jump = self.emit_jump(OpCode::JumpIfFalse, span);
+            let end = self.emit_jump(OpCode::Jump, span);
+            self.patch_jump(right_jump, span);
+            end
+        } else {
+            self.emit_jump(OpCode::JumpIfFalse, span)
+        };
+        self.emit(OpCode::Pop, span);
+        self.compile_expr(right);
+        self.patch_jump(end, span);
+    }
+
+    fn visit_expr_variable(&mut self, _id: ExprId, name: &Item) {
+        self.named_variable(name, false);
+    }
+
+    fn visit_expr_assign(&mut self, _id: ExprId, name: &Item, value: &Expr) {
+        self.compile_expr(value);
+        self.named_variable(name, true);
+    }
+
+    fn visit_expr_call(&mut self, callee: &Expr, paren: &Item, arguments: &[Expr]) {
+        self.compile_expr(callee);
+        for argument in arguments {
+            self.compile_expr(argument);
+        }
+        self.emit_with(OpCode::Call, arguments.len() as u8, paren.span);
+    }
+
+    fn visit_expr_get(&mut self, object: &Expr, name: &Item) {
+        self.compile_expr(object);
+        let name_constant = self.identifier_constant(name);
+        self.emit_with(OpCode::GetProperty, name_constant, name.span);
+    }
+
+    fn visit_expr_set(&mut self, object: &Expr, name: &Item, value: &Expr) {
+        self.compile_expr(object);
+        self.compile_expr(value);
+        let name_constant = self.identifier_constant(name);
+        self.emit_with(OpCode::SetProperty, name_constant, name.span);
+    }
+
+    fn visit_expr_this(&mut self, _id: ExprId, keyword: &Item) {
+        self.named_variable(keyword, false);
+    }
+
+    // Methods find the superclass in the `super` variable of the scope wrapping the class body.
+    fn visit_expr_super(&mut self, _id: ExprId, keyword: &Item, method: &Item) {
+        let this = Item::new("this".to_string(), Token::This, keyword.span);
+        self.named_variable(&this, false);
+        self.named_variable(keyword, false);
+        let name_constant = self.identifier_constant(method);
+        self.emit_with(OpCode::GetSuper, name_constant, method.span);
+    }
+}
+
+impl stmt::Visitor<()> for Compiler {
+    fn visit_stmt_expression(&mut self, expr: &Expr) {
+        self.compile_expr(expr);
+        self.emit(OpCode::Pop, expr.span);
+    }
+
+    fn visit_stmt_print(&mut self, expr: &Expr) {
+        self.compile_expr(expr);
+        self.emit(OpCode::Print, expr.span);
+    }
+
+    // The initializer is compiled before the local is added, the resolver having already
+    // rejected initializers that read the variable they declare.
+    fn visit_stmt_var(&mut self, name: &Item, initializer: Option<&Expr>) {
+        match initializer {
+            Some(initializer) => self.compile_expr(initializer),
+            None => self.emit(OpCode::Nil, name.span),
+        }
+        let global = self.declare_variable(name);
+        self.define_variable(global, name.span);
+    }
+
+    fn visit_stmt_block(&mut self, statements: &[Stmt]) {
+        self.begin_scope();
+        for statement in statements {
+            self.compile_stmt(statement);
+        }
+        self.end_scope();
+    }
+
+    fn visit_stmt_if(&mut self, condition: &Expr, then_branch: &Stmt, else_branch: Option<&Stmt>) {
+        self.compile_expr(condition);
+        let span = condition.span;
+        let then_jump = self.emit_jump(OpCode::JumpIfFalse, span);
+        self.emit(OpCode::Pop, span);
+        self.compile_stmt(then_branch);
+        let else_jump = self.emit_jump(OpCode::Jump, span);
+        self.patch_jump(then_jump, span);
+        self.emit(OpCode::Pop, span);
+        if let Some(else_branch) = else_branch {
+            self.compile_stmt(else_branch);
+        }
+        self.patch_jump(else_jump, span);
+    }
+
+    fn visit_stmt_while(&mut self, condition: &Expr, body: &Stmt) {
+        let start = self.chunk().code.len();
+        self.compile_expr(condition);
+        let span = condition.span;
+        let exit = self.emit_jump(OpCode::JumpIfFalse, span);
+        self.emit(OpCode::Pop, span);
+        self.compile_stmt(body);
+        self.emit_loop(start, span);
+        self.patch_jump(exit, span);
+        self.emit(OpCode::Pop, span);
+    }
+
+    // Declared before the body is compiled, so that the function can call itself.
+    fn visit_stmt_function(&mut self, declaration: &Rc<FunctionDecl>) {
+        let global = self.declare_variable(&declaration.name);
+        self.function(declaration, FunctionKind::Function);
+        self.define_variable(global, declaration.name.span);
+    }
+
+    fn visit_stmt_return(&mut self, keyword: &Item, value: Option<&Expr>) {
+        match value {
+            Some(value) => {
+                self.compile_expr(value);
+                self.emit(OpCode::Return, keyword.span);
+            }
+            None => self.emit_return(keyword.span),
+        }
+    }
+
+    fn visit_stmt_class(
+        &mut self,
+        name: &Item,
+        superclass: Option<&Expr>,
+        methods: &[Rc<FunctionDecl>],
+    ) {
+        let name_constant = self.identifier_constant(name);
+        let global = self.declare_variable(name);
+        self.emit_with(OpCode::Class, name_constant, name.span);
+        self.define_variable(global, name.span);
+
+        // The superclass stays on the stack as a local named `super` that methods capture.
+        if let Some(superclass) = superclass {
+            self.compile_expr(superclass);
+            self.begin_scope();
+            let keyword = Item::new("super".to_string(), Token::Super, superclass.span);
+            self.add_local(&keyword);
+            self.named_variable(name, false);
+            self.emit(OpCode::Inherit, superclass.span);
+        }
+
+        self.named_variable(name, false);
+        for method in methods {
+            let kind = if method.name.lexeme == "init" {
+                FunctionKind::Initializer
+            } else {
+                FunctionKind::Method
+            };
+            self.function(method, kind);
+            let method_constant = self.identifier_constant(&method.name);
+            self.emit_with(OpCode::Method, method_constant, method.name.span);
+        }
+        self.emit(OpCode::Pop, name.span);
+
+        if superclass.is_some() {
+            self.end_scope();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::ErrorCode;
+    use crate::expr::Visitor;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+    use crate::vm::chunk::OpCode;
+    use crate::vm::compiler::Compiler;
+    use crate::vm::heap::Heap;
+
+    // The script's bytecode, or the codes of the errors compiling it.
+    fn compile(source: &str) -> Result<Vec<u8>, Vec<ErrorCode>> {
+        let statements = Parser::new(Scanner::new(source).scan_tokens().expect("Failed scanning"))
+            .parse()
+            .expect("Failed parsing");
+        let mut heap = Heap::default();
+        Compiler::new()
+            .compile(&statements, &mut heap)
+            .map(|script| heap.function(script).chunk.code.clone())
+            .map_err(|errors| errors.iter().map(|error| error.code).collect())
+    }
+
+    #[test]
+    fn compile_locals_and_jumps() {
+        use OpCode::*;
+        let code = compile("{ var a = 1; if (a) a = 2; }");
+        let expected = vec![
+            Constant as u8,
+            0,
+            GetLocal as u8,
+            1,
+            JumpIfFalse as u8,
+            0,
+            9,
+            Pop as u8,
+            Constant as u8,
+            1,
+            SetLocal as u8,
+            1,
+            Pop as u8,
+            Jump as u8,
+            0,
+            1,
+            Pop as u8,
+            Pop as u8,
+            Nil as u8,
+            Return as u8,
+        ];
+        assert_eq!(Ok(expected), code);
+    }
+
+    #[test]
+    fn compile_reports_limits() {
+        let constants = (0..300).map(|n| format!("{};", n)).collect::<String>();
+        assert_eq!(
+            Err(vec![ErrorCode::TooManyConstants; 44]),
+            compile(&constants)
+        );
+        let locals = (0..300).map(|n| format!("var a{};", n)).collect::<String>();
+        assert_eq!(
+            Err(vec![ErrorCode::TooManyLocals; 45]),
+            compile(&format!("{{ {} }}", locals))
+        );
+    }
+
+    #[test]
+    fn compile_captures() {
+        use OpCode::*;
+        let code = compile("{ var a; fun f() { a; } }");
+        let expected = vec![
+            Nil as u8,
+            Closure as u8,
+            0,
+            1,
+            1,
+            Pop as u8,
+            CloseUpvalue as u8,
+            Nil as u8,
+            Return as u8,
+        ];
+        assert_eq!(Ok(expected), code);
+    }
+}
@@ -0,0 +1,173 @@
+use crate::vm::chunk::{Chunk, OpCode};
+use crate::vm::heap::Heap;
+use crate::vm::object::{ObjRef, Object, Value};
+
+/// Lists the bytecode of `function`, then that of every function declared in it, each under a
+/// `== name ==` header.
+pub fn disassemble(heap: &Heap, function: ObjRef) -> String {
+    let mut output = String::new();
+    disassemble_function(heap, function, &mut output);
+    output
+}
+
+fn disassemble_function(heap: &Heap, function: ObjRef, output: &mut String) {
+    output.push_str(&format!(
+        "== {} ==\n",
+        heap.display(Value::Object(function))
+    ));
+    let chunk = &heap.function(function).chunk;
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        let (text, next) = instruction(heap, chunk, offset);
+        output.push_str(&text);
+        output.push('\n');
+        offset = next;
+    }
+
+    for constant in chunk.constants.iter() {
+        if let Value::Object(object) = constant {
+            if let Object::Function(_) = heap.get(*object) {
+                output.push('\n');
+                disassemble_function(heap, *object, output);
+            }
+        }
+    }
+}
+
+/// The instruction at `offset` as one line: its offset, its source line (`|` when the same as
+/// the previous byte's), its opcode and operands. Returns the offset of the next instruction.
+pub fn instruction(heap: &Heap, chunk: &Chunk, offset: usize) -> (String, usize) {
+    let line = chunk.line(offset);
+    let prefix = if offset > 0 && chunk.line(offset - 1) == line {
+        format!("{:04}    | ", offset)
+    } else {
+        format!("{:04} {:>4} ", offset, line)
+    };
+    let byte = chunk.code[offset];
+    let op = match OpCode::from_byte(byte) {
+        Some(op) => op,
+        None => return (format!("{}Unknown opcode {}", prefix, byte), offset + 1),
+    };
+    let name = format!("{:<16}", format!("{:?}", op));
+    let operand = |distance: usize| chunk.code[offset + distance];
+
+    let (text, length) = match op {
+        OpCode::Constant
+        | OpCode::GetGlobal
+        | OpCode::DefineGlobal
+        | OpCode::SetGlobal
+        | OpCode::GetProperty
+        | OpCode::SetProperty
+        | OpCode::GetSuper
+        | OpCode::Class
+        | OpCode::Method => {
+            let index = operand(1);
+            let constant = heap.display(chunk.constants[index as usize]);
+            (format!("{}{:>4} '{}'", name, index, constant), 2)
+        }
+        // Each captured variable gets a line of its own after the closure's.
+        OpCode::Closure => {
+            let index = operand(1);
+            let function = chunk.constants[index as usize];
+            let mut text = format!("{}{:>4} '{}'", name, index, heap.display(function));
+            let count = match function {
+                Value::Object(function) => heap.function(function).upvalue_count,
+                _ => 0,
+            };
+            for upvalue in 0..count {
+                let kind = if operand(2 + upvalue * 2) == 1 {
+                    "local"
+                } else {
+                    "upvalue"
+                };
+                text.push_str(&format!(
+                    "\n{:04}    |                     {} {}",
+                    offset + 2 + upvalue * 2,
+                    kind,
+                    operand(3 + upvalue * 2)
+                ));
+            }
+            (text, 2 + count * 2)
+        }
+        OpCode::GetLocal
+        | OpCode::SetLocal
+        | OpCode::GetUpvalue
+        | OpCode::SetUpvalue
+        | OpCode::Call => (format!("{}{:>4}", name, operand(1)), 2),
+        OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => {
+            let distance = (operand(1) as usize) << 8 | operand(2) as usize;
+            let target = if op == OpCode::Loop {
+                offset + 3 - distance
+            } else {
+                offset + 3 + distance
+            };
+            (format!("{}{:>4} -> {}", name, offset, target), 3)
+        }
+        _ => (name.trim_end().to_string(), 1),
+    };
+    (prefix + &text, offset + length)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expr::Visitor;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+    use crate::vm::compiler::Compiler;
+    use crate::vm::disassembler::disassemble;
+    use crate::vm::heap::Heap;
+
+    #[test]
+    fn disassemble_functions() {
+        let source = "\
+fun twice(n) {
+  return n * 2;
+}
+while (false) print twice(\"a\");
+{
+  var a = 1;
+  fun get() { return a; }
+}";
+        let statements = Parser::new(Scanner::new(source).scan_tokens().unwrap())
+            .parse()
+            .unwrap();
+        let mut heap = Heap::default();
+        let script = Compiler::new().compile(&statements, &mut heap).unwrap();
+        let expected = "\
+== <script> ==
+0000    1 Closure            1 '<fn twice>'
+0002    | DefineGlobal       0 'twice'
+0004    4 False
+0005    | JumpIfFalse        5 -> 19
+0008    | Pop
+0009    | GetGlobal          0 'twice'
+0011    | Constant           2 'a'
+0013    | Call               1
+0015    | Print
+0016    | Loop              16 -> 4
+0019    | Pop
+0020    6 Constant           3 '1'
+0022    7 Closure            4 '<fn get>'
+0024    |                     local 1
+0026    | Pop
+0027    | CloseUpvalue
+0028    | Nil
+0029    | Return
+
+== <fn twice> ==
+0000    2 GetLocal           1
+0002    | Constant           0 '2'
+0004    | Multiply
+0005    | Return
+0006    1 Nil
+0007    | Return
+
+== <fn get> ==
+0000    7 GetUpvalue         0
+0002    | Return
+0003    | Nil
+0004    | Return
+";
+        assert_eq!(expected, disassemble(&heap, script));
+    }
+}
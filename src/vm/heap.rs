@@ -0,0 +1,373 @@
+use crate::vm::object::{Closure, Function, ObjRef, Object, Upvalue, Value};
+use std::collections::HashMap;
+use std::mem;
+
+// Collections start once this much has been allocated, and then whenever the heap has grown to
+// `GROWTH_FACTOR` times what survived the previous one.
+const INITIAL_THRESHOLD: usize = 1024 * 1024;
+const GROWTH_FACTOR: usize = 2;
+
+struct Header {
+    // Reached from the roots during the current collection: grey while on the worklist, black
+    // once its references were traced too.
+    marked: bool,
+    // Estimated when the object is allocated, and again whenever it grows.
+    size: usize,
+    object: Object,
+}
+
+/// Every object the virtual machine allocated. Handles stay valid for as long as the object is
+/// reachable from the roots the virtual machine marks before calling `collect`; the slots of
+/// the others are reused.
+pub struct Heap {
+    objects: Vec<Option<Header>>,
+    free: Vec<usize>,
+    // Weak: a string that is only referred to from here is freed like any other.
+    strings: HashMap<String, ObjRef>,
+    gray: Vec<ObjRef>,
+    bytes_allocated: usize,
+    next_gc: usize,
+    stress: bool,
+}
+
+impl Default for Heap {
+    fn default() -> Heap {
+        Heap {
+            objects: vec![],
+            free: vec![],
+            strings: HashMap::new(),
+            gray: vec![],
+            bytes_allocated: 0,
+            next_gc: INITIAL_THRESHOLD,
+            stress: false,
+        }
+    }
+}
+
+impl Heap {
+    /// Asks for a collection before every allocation, to catch objects that are not rooted.
+    pub fn set_stress(&mut self, stress: bool) {
+        self.stress = stress;
+    }
+
+    pub fn should_collect(&self) -> bool {
+        self.stress || self.bytes_allocated > self.next_gc
+    }
+
+    /// How many objects are allocated, whether reachable or not.
+    pub fn live_objects(&self) -> usize {
+        self.objects.len() - self.free.len()
+    }
+
+    pub fn alloc(&mut self, object: Object) -> ObjRef {
+        let size = mem::size_of::<Header>() + size_of_contents(&object);
+        self.bytes_allocated += size;
+        let header = Some(Header {
+            marked: false,
+            size,
+            object,
+        });
+        match self.free.pop() {
+            Some(index) => {
+                self.objects[index] = header;
+                ObjRef(index)
+            }
+            None => {
+                self.objects.push(header);
+                ObjRef(self.objects.len() - 1)
+            }
+        }
+    }
+
+    /// The one string object holding `text`.
+    pub fn intern(&mut self, text: &str) -> ObjRef {
+        if let Some(string) = self.strings.get(text) {
+            return *string;
+        }
+        let string = self.alloc(Object::String(text.to_string()));
+        self.strings.insert(text.to_string(), string);
+        string
+    }
+
+    fn header(&self, object: ObjRef) -> &Header {
+        match &self.objects[object.0] {
+            Some(header) => header,
+            None => panic!("Use of a freed object"),
+        }
+    }
+
+    pub fn get(&self, object: ObjRef) -> &Object {
+        &self.header(object).object
+    }
+
+    pub fn get_mut(&mut self, object: ObjRef) -> &mut Object {
+        match &mut self.objects[object.0] {
+            Some(header) => &mut header.object,
+            None => panic!("Use of a freed object"),
+        }
+    }
+
+    /// Estimates the size of `object` again after it grew, e.g. by a field or a method, so that
+    /// the next collection is due once the heap really has doubled.
+    pub fn resize(&mut self, object: ObjRef) {
+        if let Some(header) = &mut self.objects[object.0] {
+            let size = mem::size_of::<Header>() + size_of_contents(&header.object);
+            self.bytes_allocated = self.bytes_allocated - header.size + size;
+            header.size = size;
+        }
+    }
+
+    pub fn string(&self, object: ObjRef) -> &str {
+        match self.get(object) {
+            Object::String(string) => string,
+            _ => panic!("Expected a string object"),
+        }
+    }
+
+    pub fn function(&self, object: ObjRef) -> &Function {
+        match self.get(object) {
+            Object::Function(function) => function,
+            _ => panic!("Expected a function object"),
+        }
+    }
+
+    pub fn closure(&self, object: ObjRef) -> &Closure {
+        match self.get(object) {
+            Object::Closure(closure) => closure,
+            _ => panic!("Expected a closure object"),
+        }
+    }
+
+    /// `value` as `print` shows it.
+    pub fn display(&self, value: Value) -> String {
+        let object = match value {
+            Value::Nil => return "nil".to_string(),
+            Value::Bool(value) => return value.to_string(),
+            Value::Number(value) => return value.to_string(),
+            Value::Object(object) => object,
+        };
+        match self.get(object) {
+            Object::String(string) => string.clone(),
+            Object::Function(function) if function.name.is_empty() => "<script>".to_string(),
+            Object::Function(function) => format!("<fn {}>", function.name),
+            Object::Closure(closure) => self.display(Value::Object(closure.function)),
+            Object::Upvalue(_) => "upvalue".to_string(),
+            Object::Native(_) => "<native fn>".to_string(),
+            Object::Class(class) => class.name.clone(),
+            Object::Instance(instance) => match self.get(instance.class) {
+                Object::Class(class) => format!("{} instance", class.name),
+                _ => "instance".to_string(),
+            },
+            Object::BoundMethod(bound) => self.display(Value::Object(bound.method)),
+        }
+    }
+
+    pub fn mark_value(&mut self, value: Value) {
+        if let Value::Object(object) = value {
+            self.mark_object(object);
+        }
+    }
+
+    /// Greys `object`: it is live, and its own references still have to be traced.
+    pub fn mark_object(&mut self, object: ObjRef) {
+        if let Some(header) = &mut self.objects[object.0] {
+            if !header.marked {
+                header.marked = true;
+                self.gray.push(object);
+            }
+        }
+    }
+
+    /// Frees every object not reachable from those marked since the last collection, and
+    /// returns how many there were.
+    pub fn collect(&mut self) -> usize {
+        while let Some(object) = self.gray.pop() {
+            self.blacken(object);
+        }
+
+        let objects = &self.objects;
+        self.strings.retain(|_, string| {
+            objects[string.0]
+                .as_ref()
+                .is_some_and(|header| header.marked)
+        });
+
+        let mut freed = 0;
+        for (index, slot) in self.objects.iter_mut().enumerate() {
+            match slot {
+                Some(header) if header.marked => header.marked = false,
+                Some(header) => {
+                    self.bytes_allocated -= header.size;
+                    *slot = None;
+                    self.free.push(index);
+                    freed += 1;
+                }
+                None => {}
+            }
+        }
+
+        self.next_gc = (self.bytes_allocated * GROWTH_FACTOR).max(INITIAL_THRESHOLD);
+        freed
+    }
+
+    // Turns a grey object black by greying everything it refers to.
+    fn blacken(&mut self, object: ObjRef) {
+        let mut references = vec![];
+        match self.get(object) {
+            Object::String(_) | Object::Native(_) | Object::Upvalue(Upvalue::Open(_)) => {}
+            Object::Function(function) => references.extend(&function.chunk.constants),
+            Object::Closure(closure) => {
+                references.push(Value::Object(closure.function));
+                references.extend(
+                    closure
+                        .upvalues
+                        .iter()
+                        .map(|upvalue| Value::Object(*upvalue)),
+                );
+            }
+            Object::Upvalue(Upvalue::Closed(value)) => references.push(*value),
+            Object::Class(class) => {
+                for (name, method) in class.methods.iter() {
+                    references.push(Value::Object(*name));
+                    references.push(Value::Object(*method));
+                }
+            }
+            Object::Instance(instance) => {
+                references.push(Value::Object(instance.class));
+                for (name, value) in instance.fields.iter() {
+                    references.push(Value::Object(*name));
+                    references.push(*value);
+                }
+            }
+            Object::BoundMethod(bound) => {
+                references.push(bound.receiver);
+                references.push(Value::Object(bound.method));
+            }
+        }
+        for reference in references {
+            self.mark_value(reference);
+        }
+    }
+}
+
+// What `object` owns besides its header.
+fn size_of_contents(object: &Object) -> usize {
+    match object {
+        Object::String(string) => string.capacity(),
+        Object::Function(function) => {
+            function.name.capacity()
+                + function.chunk.code.capacity()
+                + function.chunk.constants.capacity() * mem::size_of::<Value>()
+        }
+        Object::Closure(closure) => closure.upvalues.capacity() * mem::size_of::<ObjRef>(),
+        Object::Class(class) => {
+            class.name.capacity() + class.methods.len() * mem::size_of::<(ObjRef, ObjRef)>()
+        }
+        Object::Instance(instance) => instance.fields.len() * mem::size_of::<(ObjRef, Value)>(),
+        Object::Upvalue(_) | Object::Native(_) | Object::BoundMethod(_) => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::heap::{Header, Heap};
+    use crate::vm::object::{Class, Function, Instance, Object, Value};
+    use std::collections::HashMap;
+    use std::mem;
+
+    #[test]
+    fn intern_and_display() {
+        let mut heap = Heap::default();
+        let a = heap.intern("a");
+        assert_eq!(a, heap.intern("a"));
+        assert_ne!(a, heap.intern("b"));
+        assert_eq!("a", heap.display(Value::Object(a)));
+
+        let class = heap.alloc(Object::Class(Class {
+            name: "Foo".to_string(),
+            methods: HashMap::new(),
+        }));
+        let instance = heap.alloc(Object::Instance(Instance {
+            class,
+            fields: HashMap::new(),
+        }));
+        let function = heap.alloc(Object::Function(Function {
+            name: "f".to_string(),
+            ..Function::default()
+        }));
+        let shown: Vec<_> = [
+            Value::Nil,
+            Value::Bool(true),
+            Value::Number(2.5),
+            Value::Object(class),
+            Value::Object(instance),
+            Value::Object(function),
+        ]
+        .iter()
+        .map(|value| heap.display(*value))
+        .collect();
+        assert_eq!(
+            vec!["nil", "true", "2.5", "Foo", "Foo instance", "<fn f>"],
+            shown
+        );
+        assert!(Value::Nil.is_falsey() && Value::Bool(false).is_falsey());
+        assert!(!Value::Number(0.0).is_falsey());
+    }
+
+    #[test]
+    fn collect_unreachable_objects() {
+        let mut heap = Heap::default();
+        let name = heap.intern("x");
+        let class = heap.alloc(Object::Class(Class {
+            name: "Foo".to_string(),
+            methods: HashMap::new(),
+        }));
+        let mut fields = HashMap::new();
+        fields.insert(name, Value::Number(1.0));
+        let instance = heap.alloc(Object::Instance(Instance { class, fields }));
+        heap.intern("garbage");
+        heap.alloc(Object::Class(Class {
+            name: "Bar".to_string(),
+            methods: HashMap::new(),
+        }));
+
+        heap.mark_object(instance);
+        assert_eq!(2, heap.collect());
+        assert_eq!(3, heap.live_objects());
+        assert_eq!("Foo instance", heap.display(Value::Object(instance)));
+        assert_eq!(name, heap.intern("x"));
+
+        // Freed slots are reused, and the table no longer hands out the freed string.
+        let again = heap.intern("garbage");
+        assert_eq!(4, heap.live_objects());
+        assert_eq!("garbage", heap.string(again));
+        assert_eq!(4, heap.collect());
+        assert_eq!(0, heap.live_objects());
+    }
+
+    #[test]
+    fn count_fields_added_after_allocation() {
+        let mut heap = Heap::default();
+        let class = heap.alloc(Object::Class(Class {
+            name: String::new(),
+            methods: HashMap::new(),
+        }));
+        let instance = heap.alloc(Object::Instance(Instance {
+            class,
+            fields: HashMap::new(),
+        }));
+        let empty = heap.bytes_allocated;
+        let name = heap.intern("x");
+        let interned = heap.bytes_allocated;
+        if let Object::Instance(instance) = heap.get_mut(instance) {
+            instance.fields.insert(name, Value::Nil);
+        }
+        heap.resize(instance);
+        assert!(heap.bytes_allocated > interned && interned > empty);
+
+        // Freeing the instance and the string gives back all they had grown to.
+        heap.mark_object(class);
+        heap.collect();
+        assert_eq!(mem::size_of::<Header>(), heap.bytes_allocated);
+    }
+}
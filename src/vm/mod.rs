@@ -0,0 +1,891 @@
+mod chunk;
+mod compiler;
+mod disassembler;
+mod heap;
+mod object;
+
+use crate::errors::{ErrorCode, LoxError};
+use crate::expr::Visitor;
+use crate::stmt::Stmt;
+use chunk::OpCode;
+use compiler::Compiler;
+use heap::Heap;
+use object::{BoundMethod, Class, Closure, Instance, Native, ObjRef, Object, Upvalue, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Calls nested deeper than this are taken to be runaway recursion.
+const MAX_FRAMES: usize = 1024;
+
+struct CallFrame {
+    closure: ObjRef,
+    // The closure's function, whose chunk is being run.
+    function: ObjRef,
+    ip: usize,
+    // Index of the stack slot holding the callee, followed by its arguments and locals.
+    slots: usize,
+}
+
+/// Runs programs compiled to bytecode on a value stack. Like the tree-walking interpreter, it
+/// keeps its globals from one program to the next.
+pub struct Vm {
+    heap: Heap,
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<ObjRef, Value>,
+    // Upvalues still pointing into the stack, so that closures capturing the same variable
+    // share one.
+    open_upvalues: Vec<ObjRef>,
+    init_string: ObjRef,
+    output: Rc<RefCell<dyn Write>>,
+    trace: bool,
+}
+
+impl Vm {
+    pub fn new() -> Vm {
+        let mut heap = Heap::default();
+        let init_string = heap.intern("init");
+        let mut vm = Vm {
+            heap,
+            stack: vec![],
+            frames: vec![],
+            globals: HashMap::new(),
+            open_upvalues: vec![],
+            init_string,
+            output: Rc::new(RefCell::new(io::stdout())),
+            trace: false,
+        };
+        vm.define_native("clock", 0, clock);
+        vm
+    }
+
+    /// Sends what `print` writes to `output` instead of stdout.
+    pub fn with_output(mut self, output: Rc<RefCell<dyn Write>>) -> Vm {
+        self.output = output;
+        self
+    }
+
+    /// Prints the stack and the instruction about to run to stderr before each instruction, and
+    /// what each garbage collection freed.
+    pub fn with_trace(mut self, trace: bool) -> Vm {
+        self.trace = trace;
+        self
+    }
+
+    /// Collects garbage before every allocation instead of once the heap has grown enough.
+    pub fn with_gc_stress(mut self, stress: bool) -> Vm {
+        self.heap.set_stress(stress);
+        self
+    }
+
+    /// Compiles and runs a resolved program, and returns the value of its final expression
+    /// statement, or nil. A runtime error unwinds every call frame, leaving the globals defined so
+    /// far in place.
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<crate::Value, Vec<LoxError>> {
+        let function = Compiler::new().compile(statements, &mut self.heap)?;
+        // The function is rooted on the stack while the closure wrapping it is allocated.
+        self.push(Value::Object(function));
+        let script = self.alloc(Object::Closure(Closure {
+            function,
+            upvalues: vec![],
+        }));
+        self.pop();
+        self.push(Value::Object(script));
+        let result = self.call(script, 0).and_then(|()| self.run());
+        if result.is_err() {
+            // Closures that escaped before the error keep the values they captured.
+            self.close_upvalues(0);
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+        result
+            .map(|value| self.export(value))
+            .map_err(|error| vec![error])
+    }
+
+    /// Every global variable with its current value, sorted by name.
+    pub fn globals(&self) -> Vec<(String, crate::Value)> {
+        let mut globals = self
+            .globals
+            .iter()
+            .map(|(name, value)| (self.heap.string(*name).to_string(), self.export(*value)))
+            .collect::<Vec<_>>();
+        globals.sort_by(|(left, _), (right, _)| left.cmp(right));
+        globals
+    }
+
+    // `value` as the tree-walking interpreter's, for callers outside the virtual machine.
+    fn export(&self, value: Value) -> crate::Value {
+        match value {
+            Value::Nil => crate::Value::Nil,
+            Value::Bool(value) => crate::Value::Bool(value),
+            Value::Number(value) => crate::Value::Number(value),
+            Value::Object(object) => match self.heap.get(object) {
+                Object::String(string) => crate::Value::String(string.clone()),
+                _ => crate::Value::Object(self.heap.display(value)),
+            },
+        }
+    }
+
+    fn define_native(&mut self, name: &'static str, arity: usize, function: fn(&[Value]) -> Value) {
+        let native = self.alloc(Object::Native(Native { arity, function }));
+        self.push(Value::Object(native));
+        let name = self.intern(name);
+        self.globals.insert(name, Value::Object(native));
+        self.pop();
+    }
+
+    // Every allocation while running goes through here, so that whatever it allocates is
+    // collected once unreachable. Objects in use must be reachable from the roots by then.
+    fn alloc(&mut self, object: Object) -> ObjRef {
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+        self.heap.alloc(object)
+    }
+
+    fn intern(&mut self, text: &str) -> ObjRef {
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+        self.heap.intern(text)
+    }
+
+    // The roots: the stack, the globals, the closures being run and the upvalues still open.
+    fn collect_garbage(&mut self) {
+        for value in self.stack.iter() {
+            self.heap.mark_value(*value);
+        }
+        for (name, value) in self.globals.iter() {
+            self.heap.mark_object(*name);
+            self.heap.mark_value(*value);
+        }
+        for frame in self.frames.iter() {
+            self.heap.mark_object(frame.closure);
+        }
+        for upvalue in self.open_upvalues.iter() {
+            self.heap.mark_object(*upvalue);
+        }
+        self.heap.mark_object(self.init_string);
+        let freed = self.heap.collect();
+        if self.trace {
+            eprintln!(
+                "-- gc: freed {} objects, {} live",
+                freed,
+                self.heap.live_objects()
+            );
+        }
+    }
+
+    fn frame(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("No call frame")
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let frame = self.frames.last_mut().expect("No call frame");
+        let byte = self.heap.function(frame.function).chunk.code[frame.ip];
+        frame.ip += 1;
+        byte
+    }
+
+    fn read_short(&mut self) -> usize {
+        let high = self.read_byte() as usize;
+        let low = self.read_byte() as usize;
+        high << 8 | low
+    }
+
+    fn read_constant(&mut self) -> Value {
+        let index = self.read_byte() as usize;
+        let function = self.frame().function;
+        self.heap.function(function).chunk.constants[index]
+    }
+
+    // Constants naming variables, properties and methods are always strings.
+    fn read_string(&mut self) -> ObjRef {
+        match self.read_constant() {
+            Value::Object(string) => string,
+            _ => panic!("Expected a string constant"),
+        }
+    }
+
+    fn read_upvalue(&mut self) -> ObjRef {
+        let index = self.read_byte() as usize;
+        let closure = self.frame().closure;
+        self.heap.closure(closure).upvalues[index]
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("Stack underflow")
+    }
+
+    fn peek(&self, distance: usize) -> Value {
+        self.stack[self.stack.len() - 1 - distance]
+    }
+
+    // Reported at the instruction being executed, which was compiled from the offending source.
+    fn error(&self, code: ErrorCode, message: &str) -> LoxError {
+        let frame = self.frames.last().expect("No call frame");
+        let chunk = &self.heap.function(frame.function).chunk;
+        LoxError::new(code, message, chunk.span(frame.ip - 1))
+    }
+
+    fn number_operands(&mut self) -> Result<(f64, f64), LoxError> {
+        match (self.peek(1), self.peek(0)) {
+            (Value::Number(left), Value::Number(right)) => {
+                self.stack.truncate(self.stack.len() - 2);
+                Ok((left, right))
+            }
+            _ => Err(self.error(ErrorCode::InvalidOperand, "Operands must be numbers.")),
+        }
+    }
+
+    fn call(&mut self, closure: ObjRef, count: usize) -> Result<(), LoxError> {
+        let function = self.heap.closure(closure).function;
+        let arity = self.heap.function(function).arity;
+        self.check_arity(arity, count)?;
+        if self.frames.len() >= MAX_FRAMES {
+            return Err(self.error(ErrorCode::StackOverflow, "Stack overflow."));
+        }
+        self.frames.push(CallFrame {
+            closure,
+            function,
+            ip: 0,
+            slots: self.stack.len() - count - 1,
+        });
+        Ok(())
+    }
+
+    fn check_arity(&self, arity: usize, count: usize) -> Result<(), LoxError> {
+        if arity != count {
+            return Err(self.error(
+                ErrorCode::ArityMismatch,
+                &format!("Expected {} arguments but got {}.", arity, count),
+            ));
+        }
+        Ok(())
+    }
+
+    // The callee sits below its `count` arguments; calls to classes and bound methods replace it
+    // with the receiver, which the method then finds in slot 0.
+    fn call_value(&mut self, callee: Value, count: usize) -> Result<(), LoxError> {
+        let object = match callee {
+            Value::Object(object) => object,
+            _ => {
+                return Err(self.error(
+                    ErrorCode::NotCallable,
+                    "Can only call functions and classes.",
+                ))
+            }
+        };
+        let callee_slot = self.stack.len() - count - 1;
+        match self.heap.get(object) {
+            Object::Closure(_) => self.call(object, count),
+            Object::Native(native) => {
+                let (arity, function) = (native.arity, native.function);
+                self.check_arity(arity, count)?;
+                let result = function(&self.stack[callee_slot + 1..]);
+                self.stack.truncate(callee_slot);
+                self.push(result);
+                Ok(())
+            }
+            Object::Class(class) => {
+                let initializer = class.methods.get(&self.init_string).copied();
+                let instance = self.alloc(Object::Instance(Instance {
+                    class: object,
+                    fields: HashMap::new(),
+                }));
+                self.stack[callee_slot] = Value::Object(instance);
+                match initializer {
+                    Some(initializer) => self.call(initializer, count),
+                    None => self.check_arity(0, count),
+                }
+            }
+            Object::BoundMethod(bound) => {
+                let (receiver, method) = (bound.receiver, bound.method);
+                self.stack[callee_slot] = receiver;
+                self.call(method, count)
+            }
+            _ => Err(self.error(
+                ErrorCode::NotCallable,
+                "Can only call functions and classes.",
+            )),
+        }
+    }
+
+    // Replaces the receiver on top of the stack with its method `name` bound to it.
+    fn bind_method(&mut self, class: ObjRef, name: ObjRef) -> Result<(), LoxError> {
+        let method = match self.heap.get(class) {
+            Object::Class(class) => class.methods.get(&name).copied(),
+            _ => None,
+        };
+        let method = match method {
+            Some(method) => method,
+            None => {
+                return Err(self.error(
+                    ErrorCode::UndefinedProperty,
+                    &format!("Undefined property '{}'.", self.heap.string(name)),
+                ))
+            }
+        };
+        let receiver = self.peek(0);
+        let bound = self.alloc(Object::BoundMethod(BoundMethod { receiver, method }));
+        self.pop();
+        self.push(Value::Object(bound));
+        Ok(())
+    }
+
+    fn instance(&self, value: Value) -> Option<ObjRef> {
+        match value {
+            Value::Object(object) if matches!(self.heap.get(object), Object::Instance(_)) => {
+                Some(object)
+            }
+            _ => None,
+        }
+    }
+
+    fn trace_instruction(&self) {
+        let stack = self
+            .stack
+            .iter()
+            .map(|value| format!("[ {} ]", self.heap.display(*value)))
+            .collect::<String>();
+        let frame = self.frames.last().expect("No call frame");
+        let chunk = &self.heap.function(frame.function).chunk;
+        let (instruction, _) = disassembler::instruction(&self.heap, chunk, frame.ip);
+        eprintln!("          {}\n{}", stack, instruction);
+    }
+
+    // Runs until the script returns, handing back what it returned.
+    fn run(&mut self) -> Result<Value, LoxError> {
+        loop {
+            if self.trace {
+                self.trace_instruction();
+            }
+            let byte = self.read_byte();
+            let op = match OpCode::from_byte(byte) {
+                Some(op) => op,
+                None => return Err(self.error(ErrorCode::Internal, "Unknown opcode.")),
+            };
+            match op {
+                OpCode::Constant => {
+                    let constant = self.read_constant();
+                    self.push(constant);
+                }
+                OpCode::Nil => self.push(Value::Nil),
+                OpCode::True => self.push(Value::Bool(true)),
+                OpCode::False => self.push(Value::Bool(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal => {
+                    let slot = self.read_byte() as usize;
+                    let index = self.frame().slots + slot;
+                    let value = self.stack[index];
+                    self.push(value);
+                }
+                OpCode::SetLocal => {
+                    let slot = self.read_byte() as usize;
+                    let index = self.frame().slots + slot;
+                    self.stack[index] = self.peek(0);
+                }
+                OpCode::GetUpvalue => {
+                    let upvalue = self.read_upvalue();
+                    let value = match self.heap.get(upvalue) {
+                        Object::Upvalue(Upvalue::Open(slot)) => self.stack[*slot],
+                        Object::Upvalue(Upvalue::Closed(value)) => *value,
+                        _ => panic!("Expected an upvalue object"),
+                    };
+                    self.push(value);
+                }
+                OpCode::SetUpvalue => {
+                    let upvalue = self.read_upvalue();
+                    let value = self.peek(0);
+                    match self.heap.get_mut(upvalue) {
+                        Object::Upvalue(Upvalue::Open(slot)) => {
+                            let slot = *slot;
+                            self.stack[slot] = value;
+                        }
+                        Object::Upvalue(closed) => *closed = Upvalue::Closed(value),
+                        _ => panic!("Expected an upvalue object"),
+                    }
+                }
+                OpCode::GetGlobal => {
+                    let name = self.read_string();
+                    match self.globals.get(&name) {
+                        Some(value) => {
+                            let value = *value;
+                            self.push(value);
+                        }
+                        None => return Err(self.undefined_variable(name)),
+                    }
+                }
+                OpCode::DefineGlobal => {
+                    let name = self.read_string();
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                }
+                OpCode::SetGlobal => {
+                    let name = self.read_string();
+                    if !self.globals.contains_key(&name) {
+                        return Err(self.undefined_variable(name));
+                    }
+                    self.globals.insert(name, self.peek(0));
+                }
+                OpCode::GetProperty => {
+                    let name = self.read_string();
+                    let instance = match self.instance(self.peek(0)) {
+                        Some(instance) => instance,
+                        None => {
+                            return Err(self.error(
+                                ErrorCode::NotAnInstance,
+                                "Only instances have properties.",
+                            ))
+                        }
+                    };
+                    let (class, field) = match self.heap.get(instance) {
+                        Object::Instance(instance) => {
+                            (instance.class, instance.fields.get(&name).copied())
+                        }
+                        _ => unreachable!(),
+                    };
+                    match field {
+                        Some(value) => {
+                            self.pop();
+                            self.push(value);
+                        }
+                        None => self.bind_method(class, name)?,
+                    }
+                }
+                OpCode::SetProperty => {
+                    let name = self.read_string();
+                    let instance = match self.instance(self.peek(1)) {
+                        Some(instance) => instance,
+                        None => {
+                            return Err(
+                                self.error(ErrorCode::NotAnInstance, "Only instances have fields.")
+                            )
+                        }
+                    };
+                    let value = self.pop();
+                    if let Object::Instance(instance) = self.heap.get_mut(instance) {
+                        instance.fields.insert(name, value);
+                    }
+                    self.heap.resize(instance);
+                    self.pop();
+                    self.push(value);
+                }
+                OpCode::GetSuper => {
+                    let name = self.read_string();
+                    let superclass = match self.pop() {
+                        Value::Object(superclass) => superclass,
+                        _ => {
+                            return Err(self.error(
+                                ErrorCode::SuperclassNotClass,
+                                "Superclass must be a class.",
+                            ))
+                        }
+                    };
+                    self.bind_method(superclass, name)?;
+                }
+                OpCode::Equal => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.push(Value::Bool(left == right));
+                }
+                OpCode::Greater => {
+                    let (left, right) = self.number_operands()?;
+                    self.push(Value::Bool(left > right));
+                }
+                OpCode::Less => {
+                    let (left, right) = self.number_operands()?;
+                    self.push(Value::Bool(left < right));
+                }
+                OpCode::Add => self.add()?,
+                OpCode::Subtract => {
+                    let (left, right) = self.number_operands()?;
+                    self.push(Value::Number(left - right));
+                }
+                OpCode::Multiply => {
+                    let (left, right) = self.number_operands()?;
+                    self.push(Value::Number(left * right));
+                }
+                OpCode::Divide => {
+                    let (left, right) = self.number_operands()?;
+                    self.push(Value::Number(left / right));
+                }
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.push(Value::Bool(value.is_falsey()));
+                }
+                OpCode::Negate => match self.peek(0) {
+                    Value::Number(value) => {
+                        self.pop();
+                        self.push(Value::Number(-value));
+                    }
+                    _ => {
+                        return Err(
+                            self.error(ErrorCode::InvalidOperand, "Operand must be a number.")
+                        )
+                    }
+                },
+                OpCode::Print => {
+                    let value = self.pop();
+                    let text = self.heap.display(value);
+                    if let Err(error) = writeln!(self.output.borrow_mut(), "{}", text) {
+                        return Err(self.error(
+                            ErrorCode::Internal,
+                            &format!("Could not write output: {}.", error),
+                        ));
+                    }
+                }
+                OpCode::Jump => {
+                    let distance = self.read_short();
+                    self.frame().ip += distance;
+                }
+                OpCode::JumpIfFalse => {
+                    let distance = self.read_short();
+                    if self.peek(0).is_falsey() {
+                        self.frame().ip += distance;
+                    }
+                }
+                OpCode::Loop => {
+                    let distance = self.read_short();
+                    self.frame().ip -= distance;
+                }
+                OpCode::Call => {
+                    let count = self.read_byte() as usize;
+                    self.call_value(self.peek(count), count)?;
+                }
+                OpCode::Closure => {
+                    let function = match self.read_constant() {
+                        Value::Object(function) => function,
+                        _ => panic!("Expected a function constant"),
+                    };
+                    let count = self.heap.function(function).upvalue_count;
+                    let mut upvalues = Vec::with_capacity(count);
+                    for _ in 0..count {
+                        let is_local = self.read_byte() == 1;
+                        let index = self.read_byte() as usize;
+                        upvalues.push(if is_local {
+                            let slot = self.frame().slots + index;
+                            self.capture_upvalue(slot)
+                        } else {
+                            let closure = self.frame().closure;
+                            self.heap.closure(closure).upvalues[index]
+                        });
+                    }
+                    let closure = self.alloc(Object::Closure(Closure { function, upvalues }));
+                    self.push(Value::Object(closure));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().expect("No call frame");
+                    self.close_upvalues(frame.slots);
+                    self.stack.truncate(frame.slots);
+                    if self.frames.is_empty() {
+                        return Ok(result);
+                    }
+                    self.push(result);
+                }
+                OpCode::Class => {
+                    let name = self.read_string();
+                    let class = self.alloc(Object::Class(Class {
+                        name: self.heap.string(name).to_string(),
+                        methods: HashMap::new(),
+                    }));
+                    self.push(Value::Object(class));
+                }
+                // Methods are copied down when the subclass is created, so that looking one up
+                // never walks the superclass chain.
+                OpCode::Inherit => {
+                    let methods = match self.peek(1) {
+                        Value::Object(superclass) => match self.heap.get(superclass) {
+                            Object::Class(superclass) => Some(superclass.methods.clone()),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    let methods = match methods {
+                        Some(methods) => methods,
+                        None => {
+                            return Err(self.error(
+                                ErrorCode::SuperclassNotClass,
+                                "Superclass must be a class.",
+                            ))
+                        }
+                    };
+                    if let Value::Object(subclass) = self.peek(0) {
+                        if let Object::Class(object) = self.heap.get_mut(subclass) {
+                            object.methods.extend(methods);
+                        }
+                        self.heap.resize(subclass);
+                    }
+                    self.pop();
+                }
+                OpCode::Method => {
+                    let name = self.read_string();
+                    if let (Value::Object(method), Value::Object(class)) =
+                        (self.peek(0), self.peek(1))
+                    {
+                        if let Object::Class(object) = self.heap.get_mut(class) {
+                            object.methods.insert(name, method);
+                        }
+                        self.heap.resize(class);
+                    }
+                    self.pop();
+                }
+            }
+        }
+    }
+
+    // The open upvalue for `slot`, reusing the one another closure already captured it with.
+    fn capture_upvalue(&mut self, slot: usize) -> ObjRef {
+        let heap = &self.heap;
+        let existing = self.open_upvalues.iter().copied().find(|upvalue| {
+            matches!(heap.get(*upvalue), Object::Upvalue(Upvalue::Open(open)) if *open == slot)
+        });
+        if let Some(upvalue) = existing {
+            return upvalue;
+        }
+        let upvalue = self.alloc(Object::Upvalue(Upvalue::Open(slot)));
+        self.open_upvalues.push(upvalue);
+        upvalue
+    }
+
+    // Moves the variables in `first` and the slots above it off the stack and into their upvalues,
+    // as they are about to go out of scope.
+    fn close_upvalues(&mut self, first: usize) {
+        let (heap, stack) = (&mut self.heap, &self.stack);
+        self.open_upvalues
+            .retain(|upvalue| match heap.get_mut(*upvalue) {
+                Object::Upvalue(open) => match *open {
+                    Upvalue::Open(slot) if slot >= first => {
+                        *open = Upvalue::Closed(stack[slot]);
+                        false
+                    }
+                    _ => true,
+                },
+                _ => true,
+            });
+    }
+
+    fn add(&mut self) -> Result<(), LoxError> {
+        let (left, right) = (self.peek(1), self.peek(0));
+        let result = match (left, right) {
+            (Value::Number(left), Value::Number(right)) => Value::Number(left + right),
+            (Value::Object(left), Value::Object(right))
+                if matches!(self.heap.get(left), Object::String(_))
+                    && matches!(self.heap.get(right), Object::String(_)) =>
+            {
+                let text = format!("{}{}", self.heap.string(left), self.heap.string(right));
+                Value::Object(self.intern(&text))
+            }
+            _ => {
+                return Err(self.error(
+                    ErrorCode::InvalidOperand,
+                    "Operands must be two numbers or two strings.",
+                ))
+            }
+        };
+        self.stack.truncate(self.stack.len() - 2);
+        self.push(result);
+        Ok(())
+    }
+
+    fn undefined_variable(&self, name: ObjRef) -> LoxError {
+        self.error(
+            ErrorCode::UndefinedVariable,
+            &format!("Undefined variable '{}'.", self.heap.string(name)),
+        )
+    }
+}
+
+/// Compiles a resolved program and lists its bytecode, without running it.
+pub fn disassemble(statements: &[Stmt]) -> Result<String, Vec<LoxError>> {
+    let mut heap = Heap::default();
+    let script = Compiler::new().compile(statements, &mut heap)?;
+    Ok(disassembler::disassemble(&heap, script))
+}
+
+impl Default for Vm {
+    fn default() -> Vm {
+        Vm::new()
+    }
+}
+
+// `clock()`: seconds elapsed since the Unix epoch, as a float.
+fn clock(_arguments: &[Value]) -> Value {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs_f64())
+        .unwrap_or(0.0);
+    Value::Number(now)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::ErrorCode;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+    use crate::vm::Vm;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn run(vm: &mut Vm, source: &str) -> Result<(), ErrorCode> {
+        let statements = Parser::new(Scanner::new(source).scan_tokens().expect("Failed scanning"))
+            .parse()
+            .expect("Failed parsing");
+        vm.interpret(&statements)
+            .map(|_| ())
+            .map_err(|errors| errors[0].code)
+    }
+
+    #[test]
+    fn run_programs() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut vm = Vm::new().with_output(output.clone());
+        let source = "\
+fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+print fib(10);
+class A { init(x) { this.x = x; } get() { return this.x; } }
+class B < A { get() { return \"b\" + super.get(); } }
+print B(\"!\").get();
+{ var a = 1; var b = a + 1; print a < b and !nil; }";
+        assert_eq!(Ok(()), run(&mut vm, source));
+        assert_eq!(
+            Ok(()),
+            run(&mut vm, "print fib; print B; print B(1); print clock;")
+        );
+        assert_eq!(
+            "55\nb!\ntrue\n<fn fib>\nB\nB instance\n<native fn>\n",
+            String::from_utf8_lossy(&output.borrow())
+        );
+    }
+
+    #[test]
+    fn hand_back_values_and_globals() {
+        let mut vm = Vm::new();
+        let mut value = |source: &str| {
+            let tokens = Scanner::new(source).scan_tokens().expect("Failed scanning");
+            let statements = Parser::new(tokens).parse().expect("Failed parsing");
+            vm.interpret(&statements)
+                .expect("Failed running")
+                .to_string()
+        };
+        assert_eq!("3", value("var a = 1; a + 2;"));
+        assert_eq!("ab", value("var b = \"a\" + \"b\"; b;"));
+        assert_eq!("nil", value("fun f() {}"));
+        assert_eq!("<fn f>", value("f;"));
+        assert_eq!("true", value("{ 1; } class A {} A() != nil;"));
+
+        let globals = vm
+            .globals()
+            .into_iter()
+            .map(|(name, value)| format!("{} = {}", name, value))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "A = A",
+                "a = 1",
+                "b = ab",
+                "clock = <native fn>",
+                "f = <fn f>"
+            ],
+            globals
+        );
+    }
+
+    #[test]
+    fn closures_share_captured_variables() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut vm = Vm::new().with_output(output.clone());
+        let source = "\
+var get; var set;
+fun make() {
+  var shared = 1;
+  fun g() { return shared; }
+  fun s(value) { shared = value; }
+  get = g; set = s;
+  print get();
+}
+make();
+set(2);
+print get();
+for (var i = 0; i < 2; i = i + 1) { var j = i; fun f() { print j; } set = f; }
+set();";
+        assert_eq!(Ok(()), run(&mut vm, source));
+        assert_eq!("1\n2\n1\n", String::from_utf8_lossy(&output.borrow()));
+    }
+
+    #[test]
+    fn collect_garbage_while_running() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut vm = Vm::new().with_output(output.clone()).with_gc_stress(true);
+        let source = "\
+class Node { init(next) { this.next = next; } }
+fun counter() { var n = 0; fun next() { n = n + 1; return n; } return next; }
+var keep = counter();
+var list = nil;
+for (var i = 0; i < 50; i = i + 1) {
+  list = Node(list);
+  var garbage = \"s\" + \"tr\";
+  keep();
+}
+list = nil;
+print keep();";
+        assert_eq!(Ok(()), run(&mut vm, source));
+        assert_eq!("51\n", String::from_utf8_lossy(&output.borrow()));
+
+        // Everything the loop allocated is gone by the next collection.
+        let live = vm.heap.live_objects();
+        assert_eq!(Ok(()), run(&mut vm, "var a = \"x\";"));
+        assert!(vm.heap.live_objects() < live, "{} objects still live", live);
+        assert!(live < 100, "{} objects still live", live);
+    }
+
+    #[test]
+    fn runtime_errors() {
+        let mut vm = Vm::new();
+        assert_eq!(
+            Err(ErrorCode::InvalidOperand),
+            run(&mut vm, "print -\"a\";")
+        );
+        assert_eq!(Err(ErrorCode::UndefinedVariable), run(&mut vm, "a = 1;"));
+        assert_eq!(Err(ErrorCode::NotCallable), run(&mut vm, "true();"));
+        assert_eq!(
+            Err(ErrorCode::ArityMismatch),
+            run(&mut vm, "class A {} A(1);")
+        );
+        assert_eq!(
+            Err(ErrorCode::StackOverflow),
+            run(&mut vm, "fun f() { f(); } f();")
+        );
+        assert_eq!(Ok(()), run(&mut vm, "var a = A();"));
+        assert_eq!(Err(ErrorCode::UndefinedProperty), run(&mut vm, "a.b;"));
+    }
+
+    #[test]
+    fn escaped_closures_survive_runtime_errors() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut vm = Vm::new().with_output(output.clone());
+        let source = "\
+var f;
+fun outer() { var x = \"c\"; fun g() { return x; } f = g; nope; }
+outer();";
+        assert_eq!(Err(ErrorCode::UndefinedVariable), run(&mut vm, source));
+        assert_eq!(Ok(()), run(&mut vm, "print f();"));
+        assert_eq!("c\n", String::from_utf8_lossy(&output.borrow()));
+    }
+}
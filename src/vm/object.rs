@@ -0,0 +1,78 @@
+use crate::vm::chunk::Chunk;
+use std::collections::HashMap;
+
+/// A value on the virtual machine's stack. Everything that does not fit lives on the heap and
+/// is referred to by handle, so values are cheap to copy.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Object(ObjRef),
+}
+
+impl Value {
+    pub fn is_falsey(&self) -> bool {
+        matches!(self, Value::Nil | Value::Bool(false))
+    }
+}
+
+/// Handle of an object on the heap. Strings are interned, so two handles are equal exactly when
+/// the values are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObjRef(pub(super) usize);
+
+pub enum Object {
+    String(String),
+    Function(Function),
+    Closure(Closure),
+    Upvalue(Upvalue),
+    Native(Native),
+    Class(Class),
+    Instance(Instance),
+    BoundMethod(BoundMethod),
+}
+
+#[derive(Default)]
+pub struct Function {
+    /// Empty for the top-level script.
+    pub name: String,
+    pub arity: usize,
+    pub upvalue_count: usize,
+    pub chunk: Chunk,
+}
+
+/// A function together with the variables it captured where it was declared.
+pub struct Closure {
+    pub function: ObjRef,
+    pub upvalues: Vec<ObjRef>,
+}
+
+/// A captured variable. It stays open, pointing at its stack slot, while the variable is in
+/// scope, and holds the value itself once it is closed.
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+pub struct Native {
+    pub arity: usize,
+    pub function: fn(&[Value]) -> Value,
+}
+
+pub struct Class {
+    pub name: String,
+    /// Closures, by the interned name of the method.
+    pub methods: HashMap<ObjRef, ObjRef>,
+}
+
+pub struct Instance {
+    pub class: ObjRef,
+    pub fields: HashMap<ObjRef, Value>,
+}
+
+/// A method looked up on an instance, remembering the instance to use as `this`.
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: ObjRef,
+}
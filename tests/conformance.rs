@@ -0,0 +1,46 @@
+use loxlib::{run_tests, Backend, Lox, TestReport};
+use std::path::Path;
+
+fn assert_passed(reports: &[TestReport]) {
+    let failures = reports
+        .iter()
+        .filter(|report| !report.passed())
+        .map(|report| {
+            format!(
+                "{}\n{}",
+                report.path.display(),
+                report.diff.as_deref().unwrap_or("")
+            )
+        })
+        .collect::<Vec<_>>();
+    assert!(
+        failures.is_empty(),
+        "{} of {} tests failed:\n\n{}",
+        failures.len(),
+        reports.len(),
+        failures.join("\n\n")
+    );
+}
+
+#[test]
+fn conformance() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lox");
+    let reports = run_tests(&corpus, Lox::new).expect("Failed reading the test corpus");
+    assert_passed(&reports);
+}
+
+#[test]
+fn conformance_on_the_vm() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lox");
+    let reports = run_tests(&corpus, || Lox::new().with_backend(Backend::Vm))
+        .expect("Failed reading the test corpus");
+    assert_passed(&reports);
+}
+
+#[test]
+fn conformance_on_the_vm_with_gc_stress() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lox");
+    let session = || Lox::new().with_backend(Backend::Vm).with_gc_stress(true);
+    let reports = run_tests(&corpus, session).expect("Failed reading the test corpus");
+    assert_passed(&reports);
+}